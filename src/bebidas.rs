@@ -0,0 +1,61 @@
+use crate::error::CafeteriaError;
+
+/// Receta de una bebida: cantidades de agua, café y espuma de cada pedido.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bebida {
+    pub nombre: &'static str,
+    /// Cantidad de agua
+    pub agua: u32,
+    /// Cantidad de cafe
+    pub cafe: u32,
+    /// Cantidad de espuma
+    pub espuma: u32,
+}
+
+/// Bebidas que pueden pedirse por su nombre.
+pub const BEBIDAS: [Bebida; 5] = [
+    Bebida {
+        nombre: "espresso",
+        agua: 3,
+        cafe: 8,
+        espuma: 1,
+    },
+    Bebida {
+        nombre: "americano",
+        agua: 9,
+        cafe: 5,
+        espuma: 1,
+    },
+    Bebida {
+        nombre: "cortado",
+        agua: 3,
+        cafe: 6,
+        espuma: 3,
+    },
+    Bebida {
+        nombre: "cappuccino",
+        agua: 3,
+        cafe: 5,
+        espuma: 6,
+    },
+    Bebida {
+        nombre: "latte",
+        agua: 3,
+        cafe: 4,
+        espuma: 9,
+    },
+];
+
+impl Bebida {
+    /// Busca en [`BEBIDAS`] la bebida con el nombre dado, sin distinguir mayúsculas.
+    ///
+    /// # Errors
+    /// * En caso de que no exista una bebida con ese nombre, devuelve [`CafeteriaError::ArgumentosInvalidos`].
+    pub fn buscar(nombre: &str) -> Result<Bebida, CafeteriaError> {
+        BEBIDAS
+            .iter()
+            .find(|bebida| bebida.nombre.eq_ignore_ascii_case(nombre.trim()))
+            .copied()
+            .ok_or(CafeteriaError::ArgumentosInvalidos)
+    }
+}
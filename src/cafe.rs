@@ -1,10 +1,12 @@
 use std::cmp::min;
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::constantes::{C, G, MAX_CANTIDAD, TIEMPO_CAFE};
-use crate::error::CafeteriaError;
+use crate::recuperacion::{acotar, bloquear, recuperar, Reparable};
+use crate::traza::{Pista, Traza};
+use crate::{debug, info};
 
 pub struct ContenedorCafe {
     /// Cantidad actual de cafe molido
@@ -13,10 +15,18 @@ pub struct ContenedorCafe {
     pub granos: u32,
     /// true si no quedan pedidos por realizar
     pub fin: bool,
+    /// true si las reposiciones se encuentran pausadas
+    pub pausado: bool,
     /// Cantidad total de cafe molido consumido
     pub cafe_consumido: u32,
     /// Cantidad total de granos de cafe consumidos
     pub granos_consumidos: u32,
+    /// Cantidad de reposiciones de cafe molido realizadas
+    pub reposiciones: u32,
+    /// Tiempo total durante el cual se estuvo reponiendo cafe molido
+    pub tiempo_reposicion: Duration,
+    /// Cantidad de veces que se recuperó el lock del contenedor tras quedar envenenado
+    pub incidentes: u32,
 }
 
 impl ContenedorCafe {
@@ -26,12 +36,30 @@ impl ContenedorCafe {
             cafe_molido: 0,
             granos: G,
             fin: false,
+            pausado: false,
             cafe_consumido: 0,
             granos_consumidos: 0,
+            reposiciones: 0,
+            tiempo_reposicion: Duration::ZERO,
+            incidentes: 0,
         }
     }
 }
 
+impl Reparable for ContenedorCafe {
+    /// Acota la cantidad de cafe molido a [`C`] y la de granos a [`G`].
+    fn reparar(&mut self) -> Vec<String> {
+        self.incidentes += 1;
+        [
+            acotar(&mut self.cafe_molido, C, "cafe molido"),
+            acotar(&mut self.granos, G, "granos"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
 impl Default for ContenedorCafe {
     fn default() -> Self {
         Self::new()
@@ -42,36 +70,43 @@ impl Default for ContenedorCafe {
 /// y el contenedor se encuentre disponible. Se rellena por completo en [`TIEMPO_CAFE`] milisegundos,
 /// durante este tiempo no se podrá utilizar el dispensador de café.
 /// También se rellena el contenedor de granos cuando su cantidad sea menor a [`C`], esto es instantáneo.
+/// Cada reposición queda registrada en la [`Traza`] recibida.
+/// No se repone mientras [`ContenedorCafe`].pausado sea true.
 /// Finaliza cuando [`ContenedorCafe`].fin es true.
-///
-/// # Errors
-/// * En caso de que el lock del contenedor se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
-pub fn rellenar_cafe(
-    contenedor: Arc<(Mutex<ContenedorCafe>, Condvar)>,
-) -> Result<(), CafeteriaError> {
+/// Si el lock del contenedor queda envenenado, se recupera mediante [`recuperar`].
+pub fn rellenar_cafe(contenedor: Arc<(Mutex<ContenedorCafe>, Condvar)>, traza: Arc<Traza>) {
     let (cafe_lock, cafe_cvar) = &*contenedor;
     loop {
-        if let Ok(mut state) = cafe_cvar.wait_while(cafe_lock.lock()?, |cont| {
-            cont.cafe_molido >= MAX_CANTIDAD && !cont.fin
-        }) {
-            if state.fin {
-                break;
-            }
-            println!("[DEBUG] Reponiendo cafe molido");
-            thread::sleep(Duration::from_millis(TIEMPO_CAFE));
-            let cantidad = min(C - state.cafe_molido, state.granos);
-            state.cafe_molido += cantidad;
-            state.granos -= cantidad;
-            state.granos_consumidos += cantidad;
-            if state.granos < C {
-                println!(
-                    "[INFO] Contenedor de granos por debajo del {}%. Reponiendo.",
-                    C * 100 / G
-                );
-                state.granos = G;
-            }
-            cafe_cvar.notify_one();
+        let mut state = recuperar(
+            cafe_lock,
+            "cafe",
+            cafe_cvar.wait_while(bloquear(cafe_lock, "cafe"), |cont| {
+                (cont.cafe_molido >= MAX_CANTIDAD || cont.pausado) && !cont.fin
+            }),
+        );
+        if state.fin {
+            break;
+        }
+        debug!("Reponiendo cafe molido");
+        let inicio = Instant::now();
+        thread::sleep(Duration::from_millis(TIEMPO_CAFE));
+        let cantidad = min(C - state.cafe_molido, state.granos);
+        state.cafe_molido += cantidad;
+        state.granos -= cantidad;
+        state.granos_consumidos += cantidad;
+        if state.granos < C {
+            info!(
+                "Contenedor de granos por debajo del {}%. Reponiendo.",
+                C * 100 / G
+            );
+            state.granos = G;
         }
+        let fin = Instant::now();
+        state.reposiciones += 1;
+        state.tiempo_reposicion += fin.saturating_duration_since(inicio);
+        traza.intervalo(Pista::RellenoCafe, "reposicion", None, inicio, fin);
+        traza.contador("cafe_molido", state.cafe_molido);
+        traza.contador("granos", state.granos);
+        cafe_cvar.notify_one();
     }
-    Ok(())
 }
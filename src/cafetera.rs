@@ -1,252 +1,1291 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::sync::{Arc, Condvar, Mutex};
+use std::io::BufReader;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::cafe::{rellenar_cafe, ContenedorCafe};
-use crate::constantes::{N, TIEMPO_PEDIDO, TIEMPO_POR_UNIDAD, TIEMPO_STATS};
-use crate::error::CafeteriaError;
+use crate::cola::ColaClientes;
+use crate::configuracion::{Configuracion, ModoApagado, PoliticaRepetidos};
+use crate::constantes::{N, TIEMPO_PEDIDO, TIEMPO_POR_UNIDAD, TIEMPO_SENAL};
+use crate::error::{CafeteriaError, DetallePedido, Motivo};
 use crate::espuma::{rellenar_espuma, ContenedorEspuma};
-use crate::pedido::Pedido;
+use crate::estadisticas::{Desperdicio, Estadisticas, EstadoDispensador};
+use crate::lectura::{Entrada, Entradas, FormatoPedidos, Rechazo};
+use crate::pedido::{Cancelacion, Linea, Pedido, Resultado};
+use crate::recuperacion::{aislar, bloquear, leer, recuperar};
+use crate::tiempos::{Latencias, TiemposPedido};
+use crate::traza::{Pista, Traza};
+use crate::utilizacion::{duracion_total, Intervalo, Ocupacion, Utilizacion};
+use crate::{debug, error, info, warn};
 
+/// Máquina de café con [`N`] dispensadores.
+///
+/// Todo su estado es compartido, por lo que clonarla devuelve otro acceso a la misma máquina,
+/// que puede enviarse a otros hilos para consultarla mientras se realizan los pedidos.
+#[derive(Clone)]
 pub struct Cafetera {
     dispensadores: Arc<(Mutex<Vec<bool>>, Condvar)>,
+    /// Clientes esperando que se les asigne un dispensador
+    cola: Arc<(Mutex<ColaClientes>, Condvar)>,
     /// Contenedor de cafe y granos
     pub cafe: Arc<(Mutex<ContenedorCafe>, Condvar)>,
     /// Contenedor de espuma y leche
     pub espuma: Arc<(Mutex<ContenedorEspuma>, Condvar)>,
     /// Cantidad total de pedidos completados
     pub cant_pedidos: Arc<Mutex<u32>>,
+    /// Cantidad de pedidos leídos y rechazados del archivo de pedidos
+    pub lectura: Arc<Mutex<(u32, u32)>>,
+    /// Línea de tiempo de la corrida
+    pub traza: Arc<Traza>,
+    /// Tiempos de cada pedido completado
+    pub tiempos: Arc<Mutex<Vec<TiemposPedido>>>,
+    /// Intervalos en que cada dispensador estuvo asignado a un pedido
+    ocupaciones: Arc<Mutex<Vec<Ocupacion>>>,
+    /// Id y resultado de cada pedido finalizado
+    pub resultados: Arc<Mutex<Vec<(usize, Resultado)>>>,
+    /// Cantidades servidas a pedidos que no se completaron
+    desperdicio: Arc<Mutex<Desperdicio>>,
+    /// Ids de los pedidos cancelados
+    cancelados: Arc<Mutex<HashSet<usize>>>,
+    /// Entradas del archivo de pedidos que no pudieron procesarse
+    rechazados: Arc<Mutex<Vec<Rechazo>>>,
+    /// Id de cada pedido fallido y mensaje del pánico que lo interrumpió
+    fallas: Arc<Mutex<Vec<(usize, String)>>>,
+    /// Ids de los pedidos recibidos y cantidad de pedidos recibidos con un id repetido
+    ids: Arc<Mutex<(BTreeSet<usize>, u32)>>,
+    /// Instantes de inicio y fin de la corrida
+    corrida: Arc<Mutex<(Option<Instant>, Option<Instant>)>>,
+    /// true si se solicitó mostrar las estadísticas inmediatamente
+    volcado: Arc<AtomicBool>,
+    /// true si se solicitó detener la cafetera
+    apagado: Arc<AtomicBool>,
+    /// Inicio de la pausa en curso, si la hay, e intervalos de las pausas ya finalizadas
+    pausa: Arc<Mutex<(Option<Instant>, Vec<Intervalo>)>>,
+    /// true cuando el hilo de estadísticas debe imprimir por última vez y finalizar
+    fin_estadisticas: Arc<(Mutex<bool>, Condvar)>,
+    configuracion: Configuracion,
+    /// Función que se llama en cada intento de preparar un pedido, luego de servir el agua
+    #[cfg(feature = "inyeccion-fallas")]
+    inyectar_falla: Option<fn(&Pedido, u32)>,
 }
 
 impl Cafetera {
     #[must_use]
     pub fn new() -> Cafetera {
+        Self::con_configuracion(Configuracion::default())
+    }
+
+    /// Crea una cafetera con los parámetros dados.
+    #[must_use]
+    pub fn con_configuracion(configuracion: Configuracion) -> Cafetera {
         Cafetera {
             dispensadores: Arc::new((Mutex::new(vec![true; N]), Condvar::new())),
+            cola: Arc::new((Mutex::new(ColaClientes::new()), Condvar::new())),
             cafe: Arc::new((Mutex::new(ContenedorCafe::new()), Condvar::new())),
             espuma: Arc::new((Mutex::new(ContenedorEspuma::new()), Condvar::new())),
             cant_pedidos: Arc::new(Mutex::new(0)),
+            lectura: Arc::new(Mutex::new((0, 0))),
+            traza: Arc::new(Traza::new()),
+            tiempos: Arc::new(Mutex::new(Vec::new())),
+            ocupaciones: Arc::new(Mutex::new(Vec::new())),
+            resultados: Arc::new(Mutex::new(Vec::new())),
+            desperdicio: Arc::new(Mutex::new(Desperdicio::default())),
+            cancelados: Arc::new(Mutex::new(HashSet::new())),
+            rechazados: Arc::new(Mutex::new(Vec::new())),
+            fallas: Arc::new(Mutex::new(Vec::new())),
+            ids: Arc::new(Mutex::new((BTreeSet::new(), 0))),
+            corrida: Arc::new(Mutex::new((None, None))),
+            volcado: Arc::new(AtomicBool::new(false)),
+            apagado: Arc::new(AtomicBool::new(false)),
+            pausa: Arc::new(Mutex::new((None, Vec::new()))),
+            fin_estadisticas: Arc::new((Mutex::new(false), Condvar::new())),
+            configuracion,
+            #[cfg(feature = "inyeccion-fallas")]
+            inyectar_falla: None,
+        }
+    }
+
+    /// Crea una cafetera con los parámetros dados que llama a la función dada en cada intento
+    /// de preparar un pedido, luego de servir el agua, con el pedido y el número de intento.
+    /// Permite simular un pánico durante la preparación en las pruebas.
+    #[cfg(feature = "inyeccion-fallas")]
+    #[must_use]
+    pub fn con_inyeccion_fallas(
+        configuracion: Configuracion,
+        inyectar_falla: fn(&Pedido, u32),
+    ) -> Cafetera {
+        Cafetera {
+            inyectar_falla: Some(inyectar_falla),
+            ..Self::con_configuracion(configuracion)
+        }
+    }
+
+    /// Parámetros con los que se creó la cafetera.
+    #[must_use]
+    pub fn configuracion(&self) -> &Configuracion {
+        &self.configuracion
+    }
+
+    /// Solicita que se muestren las estadísticas inmediatamente, sin esperar al próximo
+    /// intervalo.
+    pub fn solicitar_estadisticas(&self) {
+        self.volcado.store(true, Ordering::Relaxed);
+    }
+
+    /// Indicador que al volverse true provoca que se muestren las estadísticas inmediatamente.
+    /// Permite registrarlo como manejador de una señal.
+    #[must_use]
+    pub fn indicador_estadisticas(&self) -> Arc<AtomicBool> {
+        self.volcado.clone()
+    }
+
+    /// Detiene la cafetera: se dejan de leer pedidos y los que se encuentran en preparación se
+    /// terminan o se interrumpen según [`Configuracion::modo_apagado`].
+    pub fn detener(&self) {
+        self.apagado.store(true, Ordering::Relaxed);
+        // Los pedidos en preparación no deben quedar esperando una reposición pausada
+        self.pausar_reposicion(false);
+    }
+
+    /// Indicador que al volverse true detiene la cafetera como [`Cafetera::detener`].
+    /// Permite registrarlo como manejador de una señal.
+    #[must_use]
+    pub fn indicador_apagado(&self) -> Arc<AtomicBool> {
+        self.apagado.clone()
+    }
+
+    /// Devuelve true si se solicitó detener la cafetera.
+    #[must_use]
+    pub fn detenida(&self) -> bool {
+        self.apagado.load(Ordering::Relaxed)
+    }
+
+    /// Cancela el pedido con el id dado. Si todavía no llegó a un dispensador se descarta, y si
+    /// se está preparando se interrumpe al terminar el paso en curso, registrando lo servido
+    /// como desperdicio. Si todavía no se recibió, se descarta al recibirlo, y si ya finalizó no
+    /// se cancela. La cancelación deja de aplicarse en cuanto el pedido finaliza, por lo que no
+    /// afecta a otro pedido que luego reutilice su id.
+    ///
+    /// # Errors
+    /// * En caso de que el lock de los pedidos cancelados, de los ids o de los resultados se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn cancelar(&self, pedido: usize) -> Result<Cancelacion, CafeteriaError> {
+        // Se retienen los cancelados hasta insertar el id, de modo que si el pedido finaliza
+        // mientras tanto, registrar lo quite luego de insertarlo
+        let mut cancelados = self.cancelados.lock()?;
+        if self.resultado(pedido)?.is_some() {
+            info!("Pedido {} ya finalizado, no se cancela", pedido);
+            return Ok(Cancelacion::Finalizado);
+        }
+        let cancelacion = if self.ids.lock()?.0.contains(&pedido) {
+            info!("Pedido {} cancelado", pedido);
+            Cancelacion::EnCurso
+        } else {
+            info!("Pedido {} cancelado, se descartara al recibirlo", pedido);
+            Cancelacion::Anticipada
+        };
+        cancelados.insert(pedido);
+        drop(cancelados);
+        self.dispensadores.1.notify_all();
+        Ok(cancelacion)
+    }
+
+    /// Devuelve las entradas del archivo de pedidos rechazadas hasta el momento, con su error.
+    ///
+    /// # Errors
+    /// * En caso de que el lock de los rechazados se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn rechazados(&self) -> Result<Vec<Rechazo>, CafeteriaError> {
+        Ok(self.rechazados.lock()?.clone())
+    }
+
+    /// Devuelve el id de cada pedido fallido junto con el mensaje del pánico que lo interrumpió.
+    ///
+    /// # Errors
+    /// * En caso de que el lock de las fallas se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn fallas(&self) -> Result<Vec<(usize, String)>, CafeteriaError> {
+        Ok(self.fallas.lock()?.clone())
+    }
+
+    /// Devuelve el resultado del pedido con el id dado, o `None` si todavía no finalizó. Si se
+    /// recibió más de un pedido con el mismo id, devuelve el del primero en finalizar.
+    ///
+    /// # Errors
+    /// * En caso de que el lock de los resultados se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn resultado(&self, pedido: usize) -> Result<Option<Resultado>, CafeteriaError> {
+        Ok(self
+            .resultados
+            .lock()?
+            .iter()
+            .find(|(id, _)| *id == pedido)
+            .map(|&(_, resultado)| resultado))
+    }
+
+    /// Devuelve true si se canceló el pedido con el id dado.
+    fn cancelado(&self, pedido: usize) -> bool {
+        self.cancelados
+            .lock()
+            .is_ok_and(|cancelados| cancelados.contains(&pedido))
+    }
+
+    /// Devuelve el resultado con el que debe interrumpirse el pedido dado, si corresponde.
+    fn interrupcion(&self, pedido: usize) -> Option<Resultado> {
+        if self.cancelado(pedido) {
+            Some(Resultado::Cancelado)
+        } else if self.abortando() {
+            Some(Resultado::Abortado)
+        } else {
+            None
+        }
+    }
+
+    /// Registra el resultado de un pedido finalizado y deja de aplicarle su cancelación, si la
+    /// tenía.
+    fn registrar(&self, pedido: usize, resultado: Resultado) {
+        if let Ok(mut resultados) = self.resultados.lock() {
+            resultados.push((pedido, resultado));
+        }
+        if let Ok(mut cancelados) = self.cancelados.lock() {
+            cancelados.remove(&pedido);
+        }
+        if resultado == Resultado::Completado {
+            if let Ok(mut cant_pedidos) = self.cant_pedidos.lock() {
+                *cant_pedidos += 1;
+            }
+        }
+    }
+
+    /// Pausa la cafetera: no se asignan dispensadores a nuevos pedidos hasta llamar a
+    /// [`Cafetera::reanudar`], pero los pedidos en preparación se terminan. Si
+    /// [`Configuracion::pausar_reposicion`] es true, también se pausan las reposiciones.
+    ///
+    /// # Errors
+    /// * En caso de que algún lock se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn pausar(&self) -> Result<(), CafeteriaError> {
+        let mut pausa = self.pausa.lock()?;
+        if pausa.0.is_some() {
+            return Ok(());
+        }
+        pausa.0 = Some(Instant::now());
+        drop(pausa);
+        if self.configuracion.pausar_reposicion {
+            self.pausar_reposicion(true);
+        }
+        info!("Cafetera pausada");
+        Ok(())
+    }
+
+    /// Reanuda la cafetera luego de [`Cafetera::pausar`].
+    ///
+    /// # Errors
+    /// * En caso de que algún lock se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn reanudar(&self) -> Result<(), CafeteriaError> {
+        let mut pausa = self.pausa.lock()?;
+        let Some(desde) = pausa.0.take() else {
+            return Ok(());
+        };
+        let hasta = Instant::now();
+        pausa.1.push((desde, hasta));
+        // No se retiene la pausa al tomar los contenedores, que snapshot toma antes que ella
+        drop(pausa);
+        self.traza
+            .intervalo(Pista::Cafetera, "pausa", None, desde, hasta);
+        self.pausar_reposicion(false);
+        self.dispensadores.1.notify_all();
+        info!("Cafetera reanudada");
+        Ok(())
+    }
+
+    /// Devuelve true si la cafetera se encuentra pausada.
+    ///
+    /// # Errors
+    /// * En caso de que el lock de la pausa se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn pausada(&self) -> Result<bool, CafeteriaError> {
+        Ok(self.pausa.lock()?.0.is_some())
+    }
+
+    /// Tiempo total que la cafetera estuvo pausada, incluyendo la pausa en curso.
+    ///
+    /// # Errors
+    /// * En caso de que el lock de la pausa se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn tiempo_pausado(&self) -> Result<Duration, CafeteriaError> {
+        let pausa = self.pausa.lock()?;
+        Ok(duracion_total(&pausa.1) + pausa.0.map_or(Duration::ZERO, |desde| desde.elapsed()))
+    }
+
+    /// Ejecuta un comando recibido por consola o de forma remota: `pause`, `resume`, `stats`,
+    /// `stop` o `cancel <id>`.
+    ///
+    /// # Errors
+    /// * En caso de que el comando no exista, devuelve [`CafeteriaError::ArgumentosInvalidos`].
+    /// * En caso de que algún lock se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn ejecutar(&self, comando: &str) -> Result<(), CafeteriaError> {
+        let mut partes = comando.split_whitespace();
+        match (partes.next().unwrap_or(""), partes.next(), partes.next()) {
+            ("cancel", Some(id), None) => self
+                .cancelar(
+                    id.parse()
+                        .map_err(|_| CafeteriaError::ArgumentosInvalidos)?,
+                )
+                .map(|_| ()),
+            (_, Some(_), _) => Err(CafeteriaError::ArgumentosInvalidos),
+            ("pause", ..) => self.pausar(),
+            ("resume", ..) => self.reanudar(),
+            ("stats", ..) => {
+                self.solicitar_estadisticas();
+                Ok(())
+            }
+            ("stop", ..) => {
+                self.detener();
+                Ok(())
+            }
+            _ => Err(CafeteriaError::ArgumentosInvalidos),
+        }
+    }
+
+    /// Pausa o reanuda las reposiciones de ambos contenedores.
+    fn pausar_reposicion(&self, pausado: bool) {
+        let (cafe_lock, cafe_cvar) = &*self.cafe;
+        bloquear(cafe_lock, "cafe").pausado = pausado;
+        cafe_cvar.notify_all();
+        let (espuma_lock, espuma_cvar) = &*self.espuma;
+        bloquear(espuma_lock, "espuma").pausado = pausado;
+        espuma_cvar.notify_all();
+    }
+
+    /// Devuelve true si los pedidos en preparación deben interrumpirse.
+    fn abortando(&self) -> bool {
+        self.detenida() && self.configuracion.modo_apagado == ModoApagado::Abortar
+    }
+
+    /// Espera hasta el instante dado, o hasta que se detenga la cafetera.
+    fn esperar_hasta(&self, fin: Instant) {
+        while !self.detenida() {
+            let ahora = Instant::now();
+            if ahora >= fin {
+                break;
+            }
+            thread::sleep((fin - ahora).min(Duration::from_millis(TIEMPO_SENAL)));
+        }
+    }
+
+    /// Devuelve el estado actual de la cafetera. Salvo el de la pausa, que se lee antes, todos
+    /// los locks se toman a la vez, por lo que los valores son consistentes entre sí. Los locks de
+    /// los contenedores se recuperan si se encuentran envenenados, y el resto, que solo guarda
+    /// contadores, se lee de todas formas, de modo que las estadísticas siguen disponibles tras
+    /// el pánico de cualquier hilo.
+    ///
+    /// Los contenedores se toman antes que los dispensadores, ya que una reposición los retiene
+    /// durante [`TIEMPO_CAFE`](crate::constantes::TIEMPO_CAFE) o
+    /// [`TIEMPO_ESPUMA`](crate::constantes::TIEMPO_ESPUMA) milisegundos y mientras tanto no
+    /// debe bloquearse la asignación ni la liberación de dispensadores.
+    #[must_use]
+    pub fn snapshot(&self) -> Estadisticas {
+        let pausa = leer(&self.pausa);
+        let pausada = pausa.0.is_some();
+        let tiempo_pausado =
+            duracion_total(&pausa.1) + pausa.0.map_or(Duration::ZERO, |desde| desde.elapsed());
+        drop(pausa);
+        let cafe = bloquear(&self.cafe.0, "cafe");
+        let espuma = bloquear(&self.espuma.0, "espuma");
+        let dispensadores = leer(&self.dispensadores.0);
+        let cant_pedidos = leer(&self.cant_pedidos);
+        let (pedidos_leidos, pedidos_rechazados) = *leer(&self.lectura);
+        let pedidos_repetidos = leer(&self.ids).1;
+        let resultados = leer(&self.resultados);
+        let clientes_en_cola = leer(&self.cola.0).pedidos.len() as u32;
+        let contar = |resultado: Resultado| {
+            resultados.iter().filter(|(_, r)| *r == resultado).count() as u32
+        };
+        Estadisticas {
+            cafe_molido: cafe.cafe_molido,
+            granos: cafe.granos,
+            espuma: espuma.espuma,
+            leche: espuma.leche,
+            cafe_consumido: cafe.cafe_consumido,
+            granos_consumidos: cafe.granos_consumidos,
+            espuma_consumida: espuma.espuma_consumida,
+            leche_consumida: espuma.leche_consumida,
+            reposiciones_cafe: cafe.reposiciones,
+            reposiciones_espuma: espuma.reposiciones,
+            dispensadores: dispensadores
+                .iter()
+                .map(|&libre| {
+                    if libre {
+                        EstadoDispensador::Libre
+                    } else {
+                        EstadoDispensador::Ocupado
+                    }
+                })
+                .collect(),
+            cant_pedidos: *cant_pedidos,
+            pedidos_leidos,
+            pedidos_rechazados,
+            pedidos_repetidos,
+            pedidos_abortados: contar(Resultado::Abortado),
+            pedidos_fallidos: contar(Resultado::Fallido),
+            pedidos_cancelados: contar(Resultado::Cancelado),
+            pedidos_vencidos: contar(Resultado::Timeout),
+            clientes_desistidos: contar(Resultado::Desistido),
+            clientes_abandonados: contar(Resultado::Abandonado),
+            ingresos_perdidos: u64::from(
+                contar(Resultado::Desistido)
+                    + contar(Resultado::Abandonado)
+                    + contar(Resultado::Timeout),
+            ) * u64::from(self.configuracion.precio),
+            desperdicio: *leer(&self.desperdicio),
+            clientes_en_cola,
+            pausada,
+            tiempo_pausado_ms: tiempo_pausado.as_millis() as u64,
+            locks_recuperados: cafe.incidentes + espuma.incidentes,
+        }
+    }
+
+    /// Devuelve true si ya finalizó la corrida de pedidos.
+    ///
+    /// # Errors
+    /// * En caso de que el lock de la corrida se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn finalizada(&self) -> Result<bool, CafeteriaError> {
+        Ok(self.corrida.lock()?.1.is_some())
+    }
+
+    /// Devuelve la utilización de los dispensadores, canillas y contenedores desde el inicio de
+    /// la corrida hasta su fin, o hasta el momento actual si todavía no finalizó.
+    ///
+    /// # Errors
+    /// * En caso de que algún lock se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn utilizacion(&self) -> Result<Utilizacion, CafeteriaError> {
+        let (inicio, fin) = *self.corrida.lock()?;
+        let duracion = match inicio {
+            Some(inicio) => fin
+                .unwrap_or_else(Instant::now)
+                .saturating_duration_since(inicio),
+            None => Duration::ZERO,
+        };
+        let reposicion_cafe = bloquear(&self.cafe.0, "cafe").tiempo_reposicion;
+        let reposicion_espuma = bloquear(&self.espuma.0, "espuma").tiempo_reposicion;
+        let mut pausas = self.pausa.lock()?.clone();
+        if let Some(desde) = pausas.0 {
+            pausas.1.push((desde, fin.unwrap_or_else(Instant::now)));
         }
+        let ocupaciones = self.ocupaciones.lock()?;
+        Ok(Utilizacion::calcular(
+            duracion,
+            N,
+            &ocupaciones,
+            reposicion_cafe,
+            reposicion_espuma,
+            &pausas.1,
+        ))
+    }
+
+    /// Lee el archivo de pedidos dado por el argumento ruta y los prepara, detectando su formato
+    /// por la extensión (ver [`FormatoPedidos::desde_ruta`]).
+    ///
+    /// # Errors
+    /// Ver [`Cafetera::realizar_pedidos_con_formato`].
+    pub fn realizar_pedidos(&self, ruta: &str) -> Result<(), CafeteriaError> {
+        self.realizar_pedidos_con_formato(ruta, FormatoPedidos::desde_ruta(ruta))
     }
 
-    /// Lee el archivo de pedidos dado por el argumento ruta y los prepara.
+    /// Lee el archivo de pedidos dado por el argumento ruta en el formato dado y los prepara.
     ///
     /// # Errors
     /// * En caso de error al abrir el archivo, devuelve [`CafeteriaError::AperturaArchivo`].
+    /// * En caso de que un archivo JSON o YAML no contenga un arreglo de pedidos, devuelve [`CafeteriaError::FormatoInvalido`].
+    /// * Ver [`Cafetera::realizar_entradas`].
+    pub fn realizar_pedidos_con_formato(
+        &self,
+        ruta: &str,
+        formato: FormatoPedidos,
+    ) -> Result<(), CafeteriaError> {
+        let file = File::open(ruta).map_err(|e| CafeteriaError::AperturaArchivo(e.into()))?;
+        self.realizar_entradas(formato.entradas(BufReader::new(file))?)
+    }
+
+    /// Prepara los pedidos de las entradas dadas. Los clientes llegan
+    /// a una cola según el momento de llegada de cada pedido, y se les asigna un dispensador en
+    /// orden de llegada a medida que se liberan.
+    ///
+    /// Si se detiene la cafetera mediante [`Cafetera::detener`], se dejan de leer pedidos, se
+    /// espera a que terminen o se interrumpan los que están en preparación y se finalizan los
+    /// hilos de reposición.
+    ///
+    /// # Errors
     /// * En caso de error al leer el archivo, devuelve [`CafeteriaError::LecturaArchivo`].
     /// * En caso de que el lock de los dispensadores se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
-    pub fn realizar_pedidos(&self, ruta: &str) -> Result<(), CafeteriaError> {
-        let file = File::open(ruta).map_err(|_| CafeteriaError::AperturaArchivo)?;
-        let file = BufReader::new(file);
-        let mut pedidos_handles = Vec::new();
+    /// * En caso de que no se pueda crear alguno de los hilos, devuelve [`CafeteriaError::CreacionHilo`].
+    pub fn realizar_entradas(&self, entradas: Entradas) -> Result<(), CafeteriaError> {
         let mut cafetera_handles = Vec::new();
+        let inicio = Instant::now();
+        *self.corrida.lock()? = (Some(inicio), None);
+
+        {
+            let cafe = bloquear(&self.cafe.0, "cafe");
+            self.traza.contador("cafe_molido", cafe.cafe_molido);
+            self.traza.contador("granos", cafe.granos);
+        }
+        {
+            let espuma = bloquear(&self.espuma.0, "espuma");
+            self.traza.contador("espuma", espuma.espuma);
+            self.traza.contador("leche", espuma.leche);
+        }
 
         let cafe = self.cafe.clone();
-        cafetera_handles.push(thread::spawn(move || {
-            if rellenar_cafe(cafe).is_err() {
-                println!("[ERROR] No se pudo rellenar cafe");
-            }
-        }));
+        let traza = self.traza.clone();
+        cafetera_handles.push(lanzar("reposicion-cafe".to_string(), move || {
+            rellenar_cafe(cafe, traza);
+        })?);
 
         let espuma = self.espuma.clone();
-        cafetera_handles.push(thread::spawn(move || {
-            if rellenar_espuma(espuma).is_err() {
-                println!("[ERROR] No se pudo rellenar espuma");
-            }
-        }));
+        let traza = self.traza.clone();
+        cafetera_handles.push(lanzar("reposicion-espuma".to_string(), move || {
+            rellenar_espuma(espuma, traza);
+        })?);
 
-        cafetera_handles.push(self.estadisticas());
+        cafetera_handles.push(self.estadisticas()?);
 
-        for line in file.lines() {
-            let line = line.map_err(|_| CafeteriaError::LecturaArchivo)?;
-            match Pedido::from_line(&line) {
-                Ok(pedido) => {
-                    let dispensador = self.obtener_dispensador(pedido.id)?;
-                    pedidos_handles.push(self.realizar_pedido(pedido, dispensador));
-                    thread::sleep(Duration::from_millis(TIEMPO_PEDIDO));
-                }
-                Err(e) => {
-                    println!("[WARN] Error al procesar el pedido: {:?}", e);
-                }
-            }
-        }
+        let cafetera = self.clone();
+        let llegadas = lanzar("llegadas".to_string(), move || {
+            let _cerrar = CerrarCola(&cafetera.cola);
+            cafetera.recibir_pedidos(entradas, inicio)
+        })?;
 
-        for h in pedidos_handles {
+        for h in self.despachar()? {
             if h.join().is_err() {
-                println!("[WARN] Error en el join de un hilo");
+                warn!("Error en el join de un hilo");
             }
         }
+        let lectura = llegadas.join().unwrap_or_else(|_| {
+            warn!("Error en el join de un hilo");
+            Ok(())
+        });
 
         let (cafe_lock, cafe_cvar) = &*self.cafe;
-        cafe_lock.lock()?.fin = true;
+        bloquear(cafe_lock, "cafe").fin = true;
         cafe_cvar.notify_all();
         let (espuma_lock, espuma_cvar) = &*self.espuma;
-        espuma_lock.lock()?.fin = true;
+        bloquear(espuma_lock, "espuma").fin = true;
         espuma_cvar.notify_all();
+        let (fin_lock, fin_cvar) = &*self.fin_estadisticas;
+        *fin_lock.lock()? = true;
+        fin_cvar.notify_all();
 
         for h in cafetera_handles {
             if h.join().is_err() {
-                println!("[WARN] Error en el join de un hilo");
+                warn!("Error en el join de un hilo");
             }
         }
 
-        Ok(())
+        self.corrida.lock()?.1 = Some(Instant::now());
+        for linea in self.utilizacion()?.lineas() {
+            info!("Utilizacion {}", linea);
+        }
+
+        lectura
     }
 
-    /// Obtiene un dispensador libre para el pedido.
+    /// Recibe los pedidos del archivo y agrega cada uno a la cola de clientes en su momento de
+    /// llegada, independientemente de la disponibilidad de los dispensadores. Los pedidos sin
+    /// llegada indicada llegan [`TIEMPO_PEDIDO`] milisegundos después del anterior.
     ///
     /// # Errors
-    /// * En caso de que el lock de los dispensadores se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
-    fn obtener_dispensador(&self, pedido: usize) -> Result<usize, CafeteriaError> {
-        let (lock, cvar) = &*(self.dispensadores);
-        println!("[DEBUG] Pedido {} esperando dispensador", pedido);
-        let mut num_disp = 0;
-        if let Ok(mut state) = cvar.wait_while(lock.lock()?, |disp| !disp.iter().any(|&x| x)) {
-            for (i, disp) in state.iter_mut().enumerate() {
-                if *disp {
-                    *disp = false;
-                    num_disp = i;
-                    println!("[DEBUG] Pedido {} en dispensador {}", pedido, i);
-                    break;
+    /// * En caso de error al leer el archivo, devuelve [`CafeteriaError::LecturaArchivo`].
+    /// * En caso de que el lock de la cola se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    fn recibir_pedidos(&self, entradas: Entradas, inicio: Instant) -> Result<(), CafeteriaError> {
+        let mut proxima = Duration::ZERO;
+        for Entrada { texto, resultado } in entradas {
+            if self.detenida() {
+                warn!("Cafetera detenida, no se leeran mas pedidos");
+                break;
+            }
+            if let Err(error @ CafeteriaError::LecturaArchivo(_)) = resultado {
+                return Err(error);
+            }
+            if let Ok(mut lectura) = self.lectura.lock() {
+                lectura.0 += 1;
+                lectura.1 += u32::from(resultado.is_err());
+            }
+            match resultado {
+                Ok(Linea::Cancelacion(id)) => {
+                    self.cancelar(id)?;
+                }
+                Ok(Linea::Pedido(pedido)) => {
+                    let llegada = pedido.llegada.map_or(proxima, Duration::from_millis);
+                    proxima = llegada + Duration::from_millis(TIEMPO_PEDIDO);
+                    self.esperar_hasta(inicio + llegada);
+                    if self.detenida() {
+                        warn!("Cafetera detenida, pedido {} descartado", pedido.id);
+                        break;
+                    }
+                    let Some(pedido) = self.admitir(pedido, texto)? else {
+                        continue;
+                    };
+                    let (lock, cvar) = &*self.cola;
+                    let mut cola = lock.lock()?;
+                    if self
+                        .configuracion
+                        .largo_cola
+                        .is_some_and(|largo| cola.pedidos.len() >= largo)
+                    {
+                        drop(cola);
+                        info!("Cola llena, el cliente del pedido {} se fue", pedido.id);
+                        self.registrar(pedido.id, Resultado::Desistido);
+                        continue;
+                    }
+                    debug!("Pedido {} llego a la cola", pedido.id);
+                    cola.pedidos.push_back((pedido, Instant::now()));
+                    cvar.notify_one();
                 }
+                Err(error) => self.rechazar(Rechazo { texto, error }),
             }
         }
-        Ok(num_disp)
+        Ok(())
     }
 
-    /// Realiza el pedido utilizando el dispensador recibido en un thread aparte,
-    /// devolviendo su correspondiente [`JoinHandle`].
-    fn realizar_pedido(&self, pedido: Pedido, dispensador: usize) -> JoinHandle<()> {
-        let dispensadores = self.dispensadores.clone();
-        let cafe = self.cafe.clone();
-        let espuma = self.espuma.clone();
-        let pedidos_lock = self.cant_pedidos.clone();
-
-        thread::spawn(move || {
-            println!("[DEBUG] Pedido {} sirviendo agua", pedido.id);
-            thread::sleep(Duration::from_millis(
-                u64::from(pedido.agua) * TIEMPO_POR_UNIDAD,
-            ));
+    /// Registra una entrada del archivo de pedidos que no pudo procesarse.
+    fn rechazar(&self, rechazo: Rechazo) {
+        warn!("Error al procesar el pedido: {}", rechazo.descripcion());
+        if let Ok(mut rechazados) = self.rechazados.lock() {
+            rechazados.push(rechazo);
+        }
+    }
 
-            if Self::servir_cafe(cafe, &pedido).is_err() {
-                println!("[WARN] Pedido {} no pudo servir cafe", pedido.id);
+    /// Registra el id del pedido recibido y, si ya se había recibido, aplica la
+    /// [`PoliticaRepetidos`] configurada. Devuelve el pedido a preparar, con un id nuevo si
+    /// corresponde, o `None` si no debe prepararse. Al renombrar, el pedido se rechaza si no
+    /// hay un id mayor a todos los recibidos.
+    ///
+    /// # Errors
+    /// * En caso de que el lock de los ids o de los resultados se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    fn admitir(&self, mut pedido: Pedido, texto: String) -> Result<Option<Pedido>, CafeteriaError> {
+        let mut ids = self.ids.lock()?;
+        if ids.0.insert(pedido.id) {
+            return Ok(Some(pedido));
+        }
+        ids.1 += 1;
+        let rechazar = |ids| {
+            drop(ids);
+            if let Ok(mut lectura) = self.lectura.lock() {
+                lectura.1 += 1;
             }
-            if Self::servir_espuma(espuma, &pedido).is_err() {
-                println!("[WARN] Pedido {} no pudo servir espuma", pedido.id);
+            let error = DetallePedido::new(Some("id"), Motivo::IdRepetido).into();
+            self.rechazar(Rechazo { texto, error });
+            Ok(None)
+        };
+        match self.configuracion.repetidos {
+            PoliticaRepetidos::Rechazar => rechazar(ids),
+            PoliticaRepetidos::Renombrar => {
+                let Some(id) = ids.0.last().and_then(|id| id.checked_add(1)) else {
+                    warn!(
+                        "Pedido {} repetido, no hay un id libre para renombrarlo",
+                        pedido.id
+                    );
+                    return rechazar(ids);
+                };
+                ids.0.insert(id);
+                warn!(
+                    "Pedido {} repetido, se preparara como pedido {}",
+                    pedido.id, id
+                );
+                pedido.id = id;
+                Ok(Some(pedido))
+            }
+            PoliticaRepetidos::Idempotente => {
+                drop(ids);
+                match self.resultado(pedido.id)? {
+                    Some(resultado) => info!(
+                        "Pedido {} ya recibido, finalizo con resultado {:?}",
+                        pedido.id, resultado
+                    ),
+                    None => info!("Pedido {} ya recibido, todavia no finalizo", pedido.id),
+                }
+                Ok(None)
             }
+        }
+    }
+
+    /// Asigna dispensadores a los clientes de la cola en orden de prioridad y llegada hasta que se cierre la
+    /// cola y no queden clientes, o se detenga la cafetera. Devuelve los [`JoinHandle`] de los
+    /// pedidos lanzados.
+    ///
+    /// # Errors
+    /// * En caso de que el lock de la cola o de los dispensadores se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    /// * En caso de que no se pueda crear el hilo de un pedido, devuelve [`CafeteriaError::CreacionHilo`].
+    fn despachar(&self) -> Result<Vec<JoinHandle<()>>, CafeteriaError> {
+        let mut handles = Vec::new();
+        while let Some((pedido, llegada)) = self.siguiente_cliente()? {
+            let plazo = self.limite(&pedido, llegada);
+            let paciencia = self.paciencia(llegada);
+            let limite = plazo.into_iter().chain(paciencia).min();
+            let Some(dispensador) = self.obtener_dispensador(pedido.id, limite)? else {
+                if self.cancelado(pedido.id) {
+                    info!("Pedido {} descartado", pedido.id);
+                    self.registrar(pedido.id, Resultado::Cancelado);
+                    continue;
+                }
+                let ahora = Instant::now();
+                if paciencia
+                    .is_some_and(|paciencia| paciencia <= ahora && Some(paciencia) == limite)
+                {
+                    info!("El cliente del pedido {} abandono la cola", pedido.id);
+                    self.registrar(pedido.id, Resultado::Abandonado);
+                    continue;
+                }
+                if plazo.is_some_and(|plazo| ahora >= plazo) {
+                    warn!("Pedido {} abandonado esperando dispensador", pedido.id);
+                    self.registrar(pedido.id, Resultado::Timeout);
+                    continue;
+                }
+                warn!("Cafetera detenida, pedido {} descartado", pedido.id);
+                break;
+            };
+            handles.push(self.realizar_pedido(pedido, dispensador, llegada, Instant::now())?);
+        }
+
+        let mut cola = self.cola.0.lock()?;
+        if self.detenida() && !cola.pedidos.is_empty() {
+            warn!(
+                "Cafetera detenida, {} clientes en cola descartados",
+                cola.pedidos.len()
+            );
+            cola.pedidos.clear();
+        }
+        Ok(handles)
+    }
 
-            println!("[INFO] Pedido {} completado!", pedido.id);
-            let (disp_lock, disp_cvar) = &*dispensadores;
-            if let Ok(mut state) = disp_lock.lock() {
-                state[dispensador] = true;
+    /// Espera al próximo cliente de la cola, que es el primero en llegar entre los de mayor
+    /// prioridad. Devuelve `None` si la cola se cerró y no quedan
+    /// clientes, o si se detiene la cafetera.
+    ///
+    /// # Errors
+    /// * En caso de que el lock de la cola se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    fn siguiente_cliente(&self) -> Result<Option<(Pedido, Instant)>, CafeteriaError> {
+        let (lock, cvar) = &*self.cola;
+        loop {
+            self.purgar_cola()?;
+            let mut cola = lock.lock()?;
+            if self.detenida() {
+                return Ok(None);
             }
-            if let Ok(mut cant_pedidos) = pedidos_lock.lock() {
-                *cant_pedidos += 1;
+            // El primero en llegar entre los de mayor prioridad
+            let siguiente = cola
+                .pedidos
+                .iter()
+                .enumerate()
+                .max_by_key(|(i, (pedido, _))| (pedido.prioridad, Reverse(*i)))
+                .map(|(i, _)| i);
+            if let Some(cliente) = siguiente.and_then(|i| cola.pedidos.remove(i)) {
+                return Ok(Some(cliente));
             }
-            disp_cvar.notify_one();
-        })
+            if cola.cerrada {
+                return Ok(None);
+            }
+            let _ = cvar.wait_timeout(cola, Duration::from_millis(TIEMPO_SENAL))?;
+        }
     }
 
-    /// Sirve cafe al pedido recibido.
+    /// Instante a partir del cual el cliente llegado a la cola en el instante dado la abandona,
+    /// según la [`Configuracion::paciencia`].
+    fn paciencia(&self, llegada: Instant) -> Option<Instant> {
+        self.configuracion
+            .paciencia
+            .map(|paciencia| llegada + Duration::from_millis(paciencia))
+    }
+
+    /// Quita de la cola a los clientes que superaron su paciencia.
     ///
     /// # Errors
-    /// * En caso de que el lock de los dispensadores se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
-    fn servir_cafe(
-        contenedor_cafe: Arc<(Mutex<ContenedorCafe>, Condvar)>,
-        pedido: &Pedido,
-    ) -> Result<(), CafeteriaError> {
-        let (cafe_lock, cafe_cvar) = &*contenedor_cafe;
-        if let Ok(mut state) = cafe_cvar.wait_while(cafe_lock.lock()?, |cont| {
-            cont.cafe_molido < pedido.cafe
-        }) {
-            println!("[DEBUG] Pedido {} sirviendo cafe", pedido.id);
-            thread::sleep(Duration::from_millis(
-                u64::from(pedido.cafe) * TIEMPO_POR_UNIDAD,
-            ));
-            state.cafe_molido -= pedido.cafe;
-            state.cafe_consumido += pedido.cafe;
-            println!("[DEBUG] Pedido {} cafe completado", pedido.id);
-            cafe_cvar.notify_all();
+    /// * En caso de que el lock de la cola se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    fn purgar_cola(&self) -> Result<(), CafeteriaError> {
+        let ahora = Instant::now();
+        let mut abandonaron = Vec::new();
+        self.cola.0.lock()?.pedidos.retain(|(pedido, llegada)| {
+            let espera = self.paciencia(*llegada).is_none_or(|limite| ahora < limite);
+            if !espera {
+                abandonaron.push(pedido.id);
+            }
+            espera
+        });
+        for id in abandonaron {
+            info!("El cliente del pedido {} abandono la cola", id);
+            self.registrar(id, Resultado::Abandonado);
         }
         Ok(())
     }
 
-    /// Sirve espuma al pedido recibido.
+    /// Instante a partir del cual el pedido llegado en el instante dado se abandona, según su
+    /// plazo o el de la [`Configuracion`].
+    fn limite(&self, pedido: &Pedido, llegada: Instant) -> Option<Instant> {
+        pedido
+            .plazo
+            .or(self.configuracion.plazo)
+            .map(|plazo| llegada + Duration::from_millis(plazo))
+    }
+
+    /// Obtiene un dispensador libre para el pedido, esperando además a que la cafetera no esté
+    /// pausada. Devuelve `None` si el pedido se cancela, se alcanza el límite dado o se detiene
+    /// la cafetera mientras se espera. Un pedido que alcanzó su límite no recibe un dispensador
+    /// aunque haya uno libre.
     ///
     /// # Errors
     /// * En caso de que el lock de los dispensadores se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
-    fn servir_espuma(
-        contenedor_espuma: Arc<(Mutex<ContenedorEspuma>, Condvar)>,
-        pedido: &Pedido,
-    ) -> Result<(), CafeteriaError> {
-        let (esp_lock, esp_cvar) = &*contenedor_espuma;
-        if let Ok(mut state) = esp_cvar.wait_while(esp_lock.lock()?, |cont| {
-            cont.espuma < pedido.espuma
-        }) {
-            println!("[DEBUG] Pedido {} sirviendo espuma", pedido.id);
-            thread::sleep(Duration::from_millis(
-                u64::from(pedido.espuma) * TIEMPO_POR_UNIDAD,
-            ));
-            state.espuma -= pedido.espuma;
-            state.espuma_consumida += pedido.espuma;
-            println!("[DEBUG] Pedido {} espuma completada", pedido.id);
-            esp_cvar.notify_all();
+    fn obtener_dispensador(
+        &self,
+        pedido: usize,
+        limite: Option<Instant>,
+    ) -> Result<Option<usize>, CafeteriaError> {
+        let (lock, cvar) = &*(self.dispensadores);
+        debug!("Pedido {} esperando dispensador", pedido);
+        let mut state = lock.lock()?;
+        loop {
+            if self.cancelado(pedido) {
+                return Ok(None);
+            }
+            self.purgar_cola()?;
+            // Un pedido vencido no debe recibir un dispensador, ya que se abandonaría luego de
+            // servir el agua
+            if limite.is_some_and(|limite| Instant::now() >= limite) {
+                return Ok(None);
+            }
+            let libre = state.iter().position(|&libre| libre);
+            if let (Some(i), false) = (libre, self.pausada()?) {
+                state[i] = false;
+                debug!("Pedido {} en dispensador {}", pedido, i);
+                return Ok(Some(i));
+            }
+            if self.detenida() {
+                return Ok(None);
+            }
+            state = cvar
+                .wait_timeout(state, Duration::from_millis(TIEMPO_SENAL))?
+                .0;
         }
-        Ok(())
     }
 
-    /// Imprime por consola el estado de la cafetera cada [`TIEMPO_STATS`] milisegundos en un hilo
-    /// aparte, devolviendo su correspondiente [`JoinHandle`].
-    fn estadisticas(&self) -> JoinHandle<()> {
-        let cafe_lock = self.cafe.clone();
-        let espuma_lock = self.espuma.clone();
-        let pedidos_lock = self.cant_pedidos.clone();
-
-        thread::spawn(move || loop {
-            let (mut cant_cafe, mut cant_granos, mut cant_espuma, mut cant_leche) = (0, 0, 0, 0);
-            let (mut cafe_cons, mut granos_cons, mut espuma_cons, mut leche_cons) = (0, 0, 0, 0);
-            let mut cant_pedidos = 0;
+    /// Realiza el pedido utilizando el dispensador recibido en un thread aparte,
+    /// devolviendo su correspondiente [`JoinHandle`]. El dispensador se libera aunque el hilo
+    /// entre en pánico. Si la preparación entra en pánico, se reintenta hasta
+    /// [`Configuracion::reintentos`] veces, y luego el pedido se registra como
+    /// [`Resultado::Fallido`] junto con el mensaje del pánico.
+    ///
+    /// # Errors
+    /// * En caso de que no se pueda crear el hilo, devuelve [`CafeteriaError::CreacionHilo`].
+    fn realizar_pedido(
+        &self,
+        pedido: Pedido,
+        dispensador: usize,
+        llegada: Instant,
+        asignacion: Instant,
+    ) -> Result<JoinHandle<()>, CafeteriaError> {
+        let cafetera = self.clone();
 
-            if let Ok(cafe_contenedor) = cafe_lock.0.lock() {
-                cant_cafe = cafe_contenedor.cafe_molido;
-                cant_granos = cafe_contenedor.granos;
-                cafe_cons = cafe_contenedor.cafe_consumido;
-                granos_cons = cafe_contenedor.granos_consumidos;
+        lanzar(format!("pedido-{}", pedido.id), move || {
+            let _liberar = LiberarDispensador {
+                dispensadores: &cafetera.dispensadores,
+                dispensador,
+            };
+            let (resultado, avance) =
+                cafetera.preparar_aislado(&pedido, dispensador, llegada, asignacion);
+            if let Ok(mut ocupaciones) = cafetera.ocupaciones.lock() {
+                ocupaciones.push(Ocupacion {
+                    dispensador,
+                    asignacion,
+                    liberacion: Instant::now(),
+                    bloqueado: avance.bloqueado,
+                    cafe: avance.cafe,
+                    espuma: avance.espuma,
+                });
             }
-            if let Ok(espuma_contenedor) = espuma_lock.0.lock() {
-                cant_espuma = espuma_contenedor.espuma;
-                cant_leche = espuma_contenedor.leche;
-                espuma_cons = espuma_contenedor.espuma_consumida;
-                leche_cons = espuma_contenedor.leche_consumida;
+            let desperdicio = avance.servido;
+            match resultado {
+                Resultado::Completado => info!("Pedido {} completado!", pedido.id),
+                Resultado::Abortado => warn!("Pedido {} abortado", pedido.id),
+                Resultado::Timeout => warn!(
+                    "Pedido {} abandonado por superar su plazo, desperdicio: agua {}, cafe {}",
+                    pedido.id, desperdicio.agua, desperdicio.cafe
+                ),
+                Resultado::Cancelado => info!(
+                    "Pedido {} interrumpido, desperdicio: agua {}, cafe {}, espuma {}",
+                    pedido.id, desperdicio.agua, desperdicio.cafe, desperdicio.espuma
+                ),
+                // Ya se informó al agotar los reintentos
+                Resultado::Fallido => {}
+                // Solo ocurren antes de llegar a un dispensador
+                Resultado::Desistido | Resultado::Abandonado => {}
             }
-            if let Ok(pedidos) = pedidos_lock.lock() {
-                cant_pedidos = *pedidos;
+            if let Ok(mut total) = cafetera.desperdicio.lock() {
+                total.agregar(desperdicio);
             }
+            cafetera.registrar(pedido.id, resultado);
+        })
+    }
 
-            println!(
-                "[INFO] Estado contenedores: cafe {}, granos {}, espuma {}, leche {}",
-                cant_cafe, cant_granos, cant_espuma, cant_leche
-            );
-            println!(
-                "[INFO] Consumo total: cafe {}, granos {}, espuma {}, leche {}",
-                cafe_cons, granos_cons, espuma_cons, leche_cons
+    /// Prepara el pedido mediante [`Cafetera::preparar`], capturando un posible pánico. Se
+    /// reintenta hasta [`Configuracion::reintentos`] veces mientras el pedido no deba
+    /// interrumpirse. Si no se logra, registra el mensaje del último pánico y
+    /// devuelve [`Resultado::Fallido`].
+    ///
+    /// Devuelve junto con el resultado el tiempo bloqueado y el tiempo sirviendo café y espuma
+    /// en todos los intentos, y las cantidades desperdiciadas: las servidas en cada intento fallido, más las del último
+    /// intento si el pedido no se completó.
+    fn preparar_aislado(
+        &self,
+        pedido: &Pedido,
+        dispensador: usize,
+        llegada: Instant,
+        asignacion: Instant,
+    ) -> (Resultado, Avance) {
+        let mut total = Avance::default();
+        let mut intento = 0;
+        loop {
+            let mut avance = Avance::default();
+            let resultado = aislar(|| {
+                self.preparar(
+                    pedido,
+                    dispensador,
+                    llegada,
+                    asignacion,
+                    intento,
+                    &mut avance,
+                )
+            });
+            total.bloqueado += avance.bloqueado;
+            total.cafe += avance.cafe;
+            total.espuma += avance.espuma;
+            let mensaje = match resultado {
+                Ok(Resultado::Completado) => return (Resultado::Completado, total),
+                Ok(resultado) => {
+                    total.servido.agregar(avance.servido);
+                    return (resultado, total);
+                }
+                Err(mensaje) => mensaje,
+            };
+            total.servido.agregar(avance.servido);
+            if intento < self.configuracion.reintentos && self.interrupcion(pedido.id).is_none() {
+                intento += 1;
+                warn!(
+                    "Pedido {} fallo: {}. Reintento {} de {}",
+                    pedido.id, mensaje, intento, self.configuracion.reintentos
+                );
+                continue;
+            }
+            let desperdicio = total.servido;
+            error!(
+                "Pedido {} fallido: {}, desperdicio: agua {}, cafe {}, espuma {}",
+                pedido.id, mensaje, desperdicio.agua, desperdicio.cafe, desperdicio.espuma
             );
-            println!("[INFO] Pedidos completados: {}", cant_pedidos);
+            if let Ok(mut fallas) = self.fallas.lock() {
+                fallas.push((pedido.id, mensaje));
+            }
+            return (Resultado::Fallido, total);
+        }
+    }
 
-            if let Ok(contenedor_espuma) = espuma_lock.0.lock() {
-                if contenedor_espuma.fin {
-                    break;
+    /// Sirve el agua, el café y la espuma del pedido en el dispensador dado, devolviendo el
+    /// resultado y registrando en `avance` las cantidades servidas, el tiempo esperando por café
+    /// o espuma y el tiempo sirviéndolos, que se conservan aunque la preparación entre en pánico. Si el pedido se cancela o la cafetera se detiene
+    /// en modo [`ModoApagado::Abortar`], el pedido se interrumpe al terminar el paso en curso.
+    /// Si se supera el plazo del pedido esperando café o espuma, se abandona.
+    fn preparar(
+        &self,
+        pedido: &Pedido,
+        dispensador: usize,
+        llegada: Instant,
+        asignacion: Instant,
+        intento: u32,
+        avance: &mut Avance,
+    ) -> Resultado {
+        let limite = self.limite(pedido, llegada);
+        debug!(
+            "Pedido {} sirviendo agua, intento {}",
+            pedido.id,
+            intento + 1
+        );
+        let inicio = Instant::now();
+        thread::sleep(Duration::from_millis(
+            u64::from(pedido.agua) * TIEMPO_POR_UNIDAD,
+        ));
+        let agua = (inicio, Instant::now());
+        self.traza.intervalo(
+            Pista::Dispensador(dispensador),
+            "agua",
+            Some(pedido.id),
+            agua.0,
+            agua.1,
+        );
+        avance.servido.agua = pedido.agua;
+        #[cfg(feature = "inyeccion-fallas")]
+        if let Some(inyectar_falla) = self.inyectar_falla {
+            inyectar_falla(pedido, intento);
+        }
+        if let Some(resultado) = self.interrupcion(pedido.id) {
+            return resultado;
+        }
+
+        let espera = Instant::now();
+        let cafe = self.servir_cafe(pedido, dispensador, limite);
+        avance.bloqueado += cafe
+            .map_or_else(Instant::now, |cafe| cafe.0)
+            .saturating_duration_since(espera);
+        let Some(cafe) = cafe else {
+            return Resultado::Timeout;
+        };
+        avance.servido.cafe = pedido.cafe;
+        avance.cafe = cafe.1.saturating_duration_since(cafe.0);
+        if let Some(resultado) = self.interrupcion(pedido.id) {
+            return resultado;
+        }
+
+        let espera = Instant::now();
+        let espuma = self.servir_espuma(pedido, dispensador, limite);
+        avance.bloqueado += espuma
+            .map_or_else(Instant::now, |espuma| espuma.0)
+            .saturating_duration_since(espera);
+        let Some(espuma) = espuma else {
+            return Resultado::Timeout;
+        };
+        avance.espuma = espuma.1.saturating_duration_since(espuma.0);
+
+        if let Ok(mut tiempos) = self.tiempos.lock() {
+            tiempos.push(TiemposPedido {
+                id: pedido.id,
+                dispensador,
+                llegada,
+                asignacion,
+                agua,
+                cafe,
+                espuma,
+                fin: Instant::now(),
+            });
+        }
+        avance.servido.espuma = pedido.espuma;
+        Resultado::Completado
+    }
+
+    /// Sirve cafe al pedido recibido, devolviendo los instantes de inicio y fin del servido, o
+    /// `None` si se alcanza el límite dado antes de que haya cafe suficiente. Si el lock del
+    /// contenedor queda envenenado, se recupera mediante [`recuperar`].
+    fn servir_cafe(
+        &self,
+        pedido: &Pedido,
+        dispensador: usize,
+        limite: Option<Instant>,
+    ) -> Option<(Instant, Instant)> {
+        let (cafe_lock, cafe_cvar) = &*self.cafe;
+        let falta = |cont: &mut ContenedorCafe| cont.cafe_molido < pedido.cafe;
+        let mut state = match limite {
+            Some(limite) => {
+                let espera = limite.saturating_duration_since(Instant::now());
+                let (state, resultado) = recuperar(
+                    cafe_lock,
+                    "cafe",
+                    cafe_cvar.wait_timeout_while(bloquear(cafe_lock, "cafe"), espera, falta),
+                );
+                // Durante una reposición el lock queda tomado, por lo que el plazo puede
+                // haberse superado aunque la espera no haya finalizado por tiempo
+                if resultado.timed_out() || Instant::now() >= limite {
+                    return None;
                 }
-            } else {
-                println!("[ERROR] Debido a un error inesperado no se seguiran mostrando las estadisticas");
-                break;
+                state
             }
+            None => recuperar(
+                cafe_lock,
+                "cafe",
+                cafe_cvar.wait_while(bloquear(cafe_lock, "cafe"), falta),
+            ),
+        };
+        debug!("Pedido {} sirviendo cafe", pedido.id);
+        let inicio = Instant::now();
+        thread::sleep(Duration::from_millis(
+            u64::from(pedido.cafe) * TIEMPO_POR_UNIDAD,
+        ));
+        state.cafe_molido -= pedido.cafe;
+        state.cafe_consumido += pedido.cafe;
+        let fin = Instant::now();
+        self.traza.intervalo(
+            Pista::Dispensador(dispensador),
+            "cafe",
+            Some(pedido.id),
+            inicio,
+            fin,
+        );
+        self.traza.contador("cafe_molido", state.cafe_molido);
+        debug!("Pedido {} cafe completado", pedido.id);
+        cafe_cvar.notify_all();
+        Some((inicio, fin))
+    }
 
-            thread::sleep(Duration::from_millis(TIEMPO_STATS));
+    /// Sirve espuma al pedido recibido, devolviendo los instantes de inicio y fin del servido, o
+    /// `None` si se alcanza el límite dado antes de que haya espuma suficiente. Si el lock del
+    /// contenedor queda envenenado, se recupera mediante [`recuperar`].
+    fn servir_espuma(
+        &self,
+        pedido: &Pedido,
+        dispensador: usize,
+        limite: Option<Instant>,
+    ) -> Option<(Instant, Instant)> {
+        let (esp_lock, esp_cvar) = &*self.espuma;
+        let falta = |cont: &mut ContenedorEspuma| cont.espuma < pedido.espuma;
+        let mut state = match limite {
+            Some(limite) => {
+                let espera = limite.saturating_duration_since(Instant::now());
+                let (state, resultado) = recuperar(
+                    esp_lock,
+                    "espuma",
+                    esp_cvar.wait_timeout_while(bloquear(esp_lock, "espuma"), espera, falta),
+                );
+                // Igual que al servir cafe, la reposición puede demorar la espera
+                if resultado.timed_out() || Instant::now() >= limite {
+                    return None;
+                }
+                state
+            }
+            None => recuperar(
+                esp_lock,
+                "espuma",
+                esp_cvar.wait_while(bloquear(esp_lock, "espuma"), falta),
+            ),
+        };
+        debug!("Pedido {} sirviendo espuma", pedido.id);
+        let inicio = Instant::now();
+        thread::sleep(Duration::from_millis(
+            u64::from(pedido.espuma) * TIEMPO_POR_UNIDAD,
+        ));
+        state.espuma -= pedido.espuma;
+        state.espuma_consumida += pedido.espuma;
+        let fin = Instant::now();
+        self.traza.intervalo(
+            Pista::Dispensador(dispensador),
+            "espuma",
+            Some(pedido.id),
+            inicio,
+            fin,
+        );
+        self.traza.contador("espuma", state.espuma);
+        debug!("Pedido {} espuma completada", pedido.id);
+        esp_cvar.notify_all();
+        Some((inicio, fin))
+    }
+
+    /// Imprime por consola el estado de la cafetera cada [`Configuracion::tiempo_stats`]
+    /// milisegundos en un hilo aparte, devolviendo su correspondiente [`JoinHandle`]. Incluye las
+    /// [`Latencias`] de los pedidos completados, en total y por dispensador.
+    /// También las imprime en cuanto se solicita mediante [`Cafetera::solicitar_estadisticas`], y
+    /// una última vez al finalizar todos los pedidos.
+    ///
+    /// # Errors
+    /// * En caso de que no se pueda crear el hilo, devuelve [`CafeteriaError::CreacionHilo`].
+    fn estadisticas(&self) -> Result<JoinHandle<()>, CafeteriaError> {
+        let cafetera = self.clone();
+        let intervalo = Duration::from_millis(self.configuracion.tiempo_stats);
+
+        lanzar("estadisticas".to_string(), move || {
+            let (fin_lock, fin_cvar) = &*cafetera.fin_estadisticas;
+            let mut proximo = Instant::now() + intervalo;
+            loop {
+                let fin = match fin_lock.lock() {
+                    Ok(mut fin) => loop {
+                        let ahora = Instant::now();
+                        if *fin
+                            || ahora >= proximo
+                            || cafetera.volcado.swap(false, Ordering::Relaxed)
+                        {
+                            break *fin;
+                        }
+                        let espera = (proximo - ahora).min(Duration::from_millis(TIEMPO_SENAL));
+                        match fin_cvar.wait_timeout(fin, espera) {
+                            Ok((guard, _)) => fin = guard,
+                            Err(_) => break true,
+                        }
+                    },
+                    Err(_) => true,
+                };
+
+                if fin {
+                    info!("Estadisticas finales");
+                }
+                cafetera.imprimir_estadisticas();
+                if fin {
+                    break;
+                }
+                proximo = Instant::now() + intervalo;
+            }
         })
     }
+
+    /// Imprime por consola el estado de la cafetera y las latencias de los pedidos completados.
+    fn imprimir_estadisticas(&self) {
+        for linea in self.snapshot().lineas() {
+            info!("{}", linea);
+        }
+        if let Ok(tiempos) = self.tiempos.lock() {
+            for linea in Latencias::calcular(tiempos.iter()).lineas() {
+                info!("{}", linea);
+            }
+            for dispensador in 0..N {
+                for linea in Latencias::por_dispensador(&tiempos, dispensador).lineas() {
+                    info!("Dispensador {} - {}", dispensador, linea);
+                }
+            }
+        }
+    }
+}
+
+/// Lo realizado al preparar un pedido, que se conserva aunque la preparación entre en pánico.
+#[derive(Default)]
+struct Avance {
+    /// Cantidades servidas
+    servido: Desperdicio,
+    /// Tiempo esperando por café o espuma
+    bloqueado: Duration,
+    /// Tiempo sirviendo café
+    cafe: Duration,
+    /// Tiempo sirviendo espuma
+    espuma: Duration,
+}
+
+/// Libera un dispensador al descartarse, incluso durante el pánico del hilo de su pedido.
+struct LiberarDispensador<'a> {
+    dispensadores: &'a (Mutex<Vec<bool>>, Condvar),
+    dispensador: usize,
+}
+
+impl Drop for LiberarDispensador<'_> {
+    fn drop(&mut self) {
+        let (lock, cvar) = self.dispensadores;
+        lock.lock().unwrap_or_else(PoisonError::into_inner)[self.dispensador] = true;
+        cvar.notify_one();
+    }
+}
+
+/// Indica que no llegarán más clientes a la cola al descartarse, incluso durante el pánico del
+/// hilo de llegadas, para que el despacho de pedidos pueda finalizar.
+struct CerrarCola<'a>(&'a (Mutex<ColaClientes>, Condvar));
+
+impl Drop for CerrarCola<'_> {
+    fn drop(&mut self) {
+        let (lock, cvar) = self.0;
+        lock.lock().unwrap_or_else(PoisonError::into_inner).cerrada = true;
+        cvar.notify_all();
+    }
+}
+
+/// Lanza un hilo con el nombre dado, devolviendo su correspondiente [`JoinHandle`].
+///
+/// # Errors
+/// * En caso de que no se pueda crear el hilo, devuelve [`CafeteriaError::CreacionHilo`].
+pub(crate) fn lanzar<F, T>(nombre: String, f: F) -> Result<JoinHandle<T>, CafeteriaError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    thread::Builder::new()
+        .name(nombre)
+        .spawn(f)
+        .map_err(|e| CafeteriaError::CreacionHilo(e.into()))
 }
 
 impl Default for Cafetera {
@@ -0,0 +1,572 @@
+use std::str::FromStr;
+
+use crate::configuracion::Configuracion;
+use crate::constantes::UMBRAL_REGRESION;
+use crate::error::CafeteriaError;
+use crate::generador::Generador;
+use crate::lectura::FormatoPedidos;
+use crate::log::Nivel;
+use crate::pedido::CANT_PEDIDOS;
+use crate::reporte::FormatoReporte;
+
+/// Nombre del programa en los mensajes de uso.
+const PROGRAMA: &str = "cafeteria";
+/// Opciones que muestran la ayuda de un comando.
+const AYUDA: [&str; 2] = ["-h", "--help"];
+/// Opción con la que se generaba un archivo de pedidos antes de existir los subcomandos.
+const GENERAR_ANTERIOR: &str = "-f";
+
+/// Opción de un subcomando.
+struct Opcion {
+    nombre: &'static str,
+    /// Nombre del valor que recibe la opción, o `None` si no recibe ninguno
+    valor: Option<&'static str>,
+    ayuda: &'static str,
+}
+
+const fn opcion(nombre: &'static str, valor: &'static str, ayuda: &'static str) -> Opcion {
+    Opcion {
+        nombre,
+        valor: Some(valor),
+        ayuda,
+    }
+}
+
+const fn bandera(nombre: &'static str, ayuda: &'static str) -> Opcion {
+    Opcion {
+        nombre,
+        valor: None,
+        ayuda,
+    }
+}
+
+const OPCIONES_CORRIDA: &[Opcion] = &[
+    bandera("-v", "Muestra los mensajes de depuración"),
+    bandera("-q", "Muestra solo advertencias y errores"),
+    bandera(
+        "--timestamps",
+        "Agrega el tiempo transcurrido a cada mensaje",
+    ),
+    bandera("--thread-names", "Agrega el nombre del hilo a cada mensaje"),
+    opcion(
+        "--log-file",
+        "ruta",
+        "Escribe todos los mensajes en el archivo",
+    ),
+    opcion(
+        "-t",
+        "ruta",
+        "Exporta la traza de la corrida en formato Chrome Trace Event",
+    ),
+    opcion(
+        "--metrics-addr",
+        "direccion",
+        "Expone métricas en formato Prometheus",
+    ),
+    opcion(
+        "--report",
+        "ruta",
+        "Escribe el reporte final en JSON, CSV o Markdown",
+    ),
+    opcion(
+        "--rejected",
+        "ruta",
+        "Escribe los pedidos rechazados con su error",
+    ),
+    opcion(
+        "--stats-interval",
+        "ms",
+        "Intervalo entre impresiones de estadísticas",
+    ),
+    opcion(
+        "--shutdown",
+        "drain|abort",
+        "Qué hacer con los pedidos en curso al detener",
+    ),
+    bandera(
+        "--pause-refills",
+        "Pausa también las reposiciones al pausar",
+    ),
+    opcion(
+        "--deadline",
+        "ms",
+        "Plazo de los pedidos que no indican uno",
+    ),
+    opcion(
+        "--queue-size",
+        "n",
+        "Cantidad máxima de clientes en la cola",
+    ),
+    opcion(
+        "--patience",
+        "ms",
+        "Tiempo máximo de espera de un cliente en la cola",
+    ),
+    opcion("--price", "n", "Precio de cada pedido"),
+    opcion(
+        "--duplicates",
+        "reject|rename|idempotent",
+        "Qué hacer con los ids repetidos",
+    ),
+    opcion("--retries", "n", "Reintentos de los pedidos que fallan"),
+    bandera("--strict", "Finaliza con error si se rechazó algún pedido"),
+];
+
+const OPCIONES_GENERADOR: &[Opcion] = &[
+    opcion(
+        "--arrivals",
+        "modelo",
+        "fixed, poisson:<t>, rush:<t>:<n> o profile:<t>@<ms>,...",
+    ),
+    opcion("--mix", "bebida=peso,...", "Proporción de cada bebida"),
+    opcion("--seed", "n", "Semilla del generador de números aleatorios"),
+];
+
+const OPCION_FORMATO: &[Opcion] = &[opcion(
+    "--format",
+    "text|json|jsonl|yaml",
+    "Formato del archivo de pedidos, por defecto según su extensión",
+)];
+
+const OPCIONES_REPORTE: &[Opcion] = &[opcion(
+    "--output",
+    "ruta",
+    "Convierte el reporte al formato de la extensión de la ruta",
+)];
+
+const OPCIONES_COMPARACION: &[Opcion] = &[opcion(
+    "--threshold",
+    "porcentaje",
+    "Variación a partir de la cual se considera una regresión",
+)];
+
+/// Subcomandos del programa.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subcomando {
+    Run,
+    Generate,
+    Validate,
+    Simulate,
+    Report,
+    Compare,
+}
+
+impl Subcomando {
+    const TODOS: [Subcomando; 6] = [
+        Subcomando::Run,
+        Subcomando::Generate,
+        Subcomando::Validate,
+        Subcomando::Simulate,
+        Subcomando::Report,
+        Subcomando::Compare,
+    ];
+
+    fn nombre(self) -> &'static str {
+        match self {
+            Subcomando::Run => "run",
+            Subcomando::Generate => "generate",
+            Subcomando::Validate => "validate",
+            Subcomando::Simulate => "simulate",
+            Subcomando::Report => "report",
+            Subcomando::Compare => "compare",
+        }
+    }
+
+    /// Argumentos posicionales obligatorios y opcionales.
+    fn posicionales(self) -> (&'static [&'static str], &'static [&'static str]) {
+        match self {
+            Subcomando::Run | Subcomando::Validate => (&["archivo"], &[]),
+            Subcomando::Generate => (&["archivo"], &["cantidad"]),
+            Subcomando::Simulate => (&[], &["cantidad"]),
+            Subcomando::Report => (&["reporte.json"], &[]),
+            Subcomando::Compare => (&["anterior.json", "actual.json"], &[]),
+        }
+    }
+
+    fn descripcion(self) -> &'static str {
+        match self {
+            Subcomando::Run => "Realiza los pedidos del archivo",
+            Subcomando::Generate => "Genera un archivo de pedidos aleatorios",
+            Subcomando::Validate => "Valida un archivo de pedidos sin correr la cafetera",
+            Subcomando::Simulate => "Realiza pedidos aleatorios sin escribirlos en un archivo",
+            Subcomando::Report => "Muestra o convierte un reporte en formato JSON",
+            Subcomando::Compare => "Compara dos reportes en formato JSON",
+        }
+    }
+
+    fn opciones(self) -> Vec<&'static Opcion> {
+        let grupos: &[&'static [Opcion]] = match self {
+            Subcomando::Run => &[OPCION_FORMATO, OPCIONES_CORRIDA],
+            Subcomando::Generate => &[OPCIONES_GENERADOR, OPCION_FORMATO],
+            Subcomando::Validate => &[OPCION_FORMATO],
+            Subcomando::Simulate => &[OPCIONES_GENERADOR, OPCIONES_CORRIDA],
+            Subcomando::Report => &[OPCIONES_REPORTE],
+            Subcomando::Compare => &[OPCIONES_COMPARACION],
+        };
+        grupos.iter().flat_map(|grupo| grupo.iter()).collect()
+    }
+
+    /// Línea de uso, con los argumentos posicionales.
+    fn sinopsis(self) -> String {
+        let (obligatorios, opcionales) = self.posicionales();
+        let mut sinopsis = self.nombre().to_string();
+        for argumento in obligatorios {
+            sinopsis.push_str(&format!(" <{}>", argumento));
+        }
+        for argumento in opcionales {
+            sinopsis.push_str(&format!(" [{}]", argumento));
+        }
+        sinopsis
+    }
+
+    /// Texto de ayuda del subcomando, con todas sus opciones.
+    #[must_use]
+    pub fn ayuda(self) -> String {
+        let mut ayuda = format!(
+            "Uso: {} {} [opciones]\n\n{}.\n\nOpciones:\n",
+            PROGRAMA,
+            self.sinopsis(),
+            self.descripcion()
+        );
+        for opcion in self.opciones() {
+            let nombre = match opcion.valor {
+                Some(valor) => format!("{} <{}>", opcion.nombre, valor),
+                None => opcion.nombre.to_string(),
+            };
+            ayuda.push_str(&format!("  {:<40} {}\n", nombre, opcion.ayuda));
+        }
+        ayuda.push_str(&format!("  {:<40} Muestra esta ayuda\n", AYUDA.join(", ")));
+        ayuda
+    }
+}
+
+impl FromStr for Subcomando {
+    type Err = CafeteriaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Subcomando::TODOS
+            .into_iter()
+            .find(|subcomando| subcomando.nombre() == s)
+            .ok_or(CafeteriaError::ArgumentosInvalidos)
+    }
+}
+
+/// Texto de ayuda general, con la lista de subcomandos.
+#[must_use]
+pub fn ayuda() -> String {
+    let mut ayuda = format!("Uso: {} <comando> [opciones]\n\nComandos:\n", PROGRAMA);
+    for subcomando in Subcomando::TODOS {
+        ayuda.push_str(&format!(
+            "  {:<40} {}\n",
+            subcomando.sinopsis(),
+            subcomando.descripcion()
+        ));
+    }
+    ayuda.push_str(&format!(
+        "\nUtilizar \"{} <comando> --help\" para ver las opciones de cada comando.\n",
+        PROGRAMA
+    ));
+    ayuda
+}
+
+/// Error al interpretar los argumentos del programa.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorUso {
+    /// Descripción del problema
+    pub mensaje: String,
+    /// Subcomando que se intentó utilizar, si se reconoció alguno
+    pub subcomando: Option<Subcomando>,
+}
+
+impl ErrorUso {
+    /// Indica cómo ver la ayuda del subcomando que se intentó utilizar.
+    #[must_use]
+    pub fn sugerencia(&self) -> String {
+        match self.subcomando {
+            Some(subcomando) => format!(
+                "Utilizar \"{} {} --help\" para ver sus opciones.",
+                PROGRAMA,
+                subcomando.nombre()
+            ),
+            None => format!("Utilizar \"{} --help\" para ver los comandos.", PROGRAMA),
+        }
+    }
+}
+
+impl From<ErrorUso> for CafeteriaError {
+    fn from(_: ErrorUso) -> Self {
+        CafeteriaError::ArgumentosInvalidos
+    }
+}
+
+/// Opciones de una corrida de la cafetera.
+#[derive(Debug, Clone, Default)]
+pub struct OpcionesCorrida {
+    pub configuracion: Configuracion,
+    /// Formato del archivo de pedidos, o `None` para elegirlo según su extensión
+    pub formato: Option<FormatoPedidos>,
+    /// Nivel de log indicado con `-v` o `-q`
+    pub nivel: Option<Nivel>,
+    pub timestamps: bool,
+    pub hilos: bool,
+    pub log: Option<String>,
+    pub traza: Option<String>,
+    pub metricas: Option<String>,
+    pub reporte: Option<String>,
+    pub rechazados: Option<String>,
+    /// Si la corrida debe finalizar con error en caso de rechazar algún pedido
+    pub estricto: bool,
+}
+
+/// Comando a ejecutar, con sus argumentos ya interpretados.
+#[derive(Debug, Clone)]
+pub enum Comando {
+    /// Mostrar el texto de ayuda dado
+    Ayuda(String),
+    Correr {
+        ruta: String,
+        opciones: OpcionesCorrida,
+    },
+    Generar {
+        ruta: String,
+        generador: Generador,
+    },
+    Validar {
+        ruta: String,
+        formato: Option<FormatoPedidos>,
+    },
+    Simular {
+        generador: Generador,
+        opciones: OpcionesCorrida,
+    },
+    Reportar {
+        ruta: String,
+        salida: Option<String>,
+    },
+    Comparar {
+        anterior: String,
+        actual: String,
+        umbral: f64,
+    },
+}
+
+/// Interpreta los argumentos del programa, sin incluir su nombre.
+///
+/// Además de los subcomandos se aceptan las formas anteriores `<archivo> [opciones]`, equivalente
+/// a `run`, y `-f <archivo> [cantidad]`, equivalente a `generate`.
+///
+/// # Errors
+/// * En caso de que falte o sobre algún argumento, de que una opción no exista o de que su valor
+///   no sea válido, devuelve un [`ErrorUso`] con la descripción del problema.
+pub fn parsear(args: &[String]) -> Result<Comando, ErrorUso> {
+    let Some(primero) = args.first() else {
+        return Ok(Comando::Ayuda(ayuda()));
+    };
+    let (subcomando, resto) = if let Ok(subcomando) = primero.parse::<Subcomando>() {
+        (subcomando, &args[1..])
+    } else if primero == GENERAR_ANTERIOR {
+        (Subcomando::Generate, &args[1..])
+    } else if AYUDA.contains(&primero.as_str()) || primero == "help" {
+        return Ok(Comando::Ayuda(ayuda()));
+    } else if !primero.starts_with('-') {
+        (Subcomando::Run, args)
+    } else {
+        return Err(ErrorUso {
+            mensaje: format!("Comando desconocido: {}", primero),
+            subcomando: None,
+        });
+    };
+    if resto.iter().any(|arg| AYUDA.contains(&arg.as_str())) {
+        return Ok(Comando::Ayuda(subcomando.ayuda()));
+    }
+    Argumentos::separar(subcomando, resto)?.comando()
+}
+
+/// Argumentos de un subcomando separados en posicionales y opciones.
+struct Argumentos {
+    subcomando: Subcomando,
+    posicionales: Vec<String>,
+    opciones: Vec<(&'static str, String)>,
+}
+
+impl Argumentos {
+    fn separar(subcomando: Subcomando, args: &[String]) -> Result<Argumentos, ErrorUso> {
+        let mut argumentos = Argumentos {
+            subcomando,
+            posicionales: Vec::new(),
+            opciones: Vec::new(),
+        };
+        let disponibles = subcomando.opciones();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                argumentos.posicionales.push(arg.clone());
+                continue;
+            }
+            let Some(opcion) = disponibles.iter().find(|opcion| opcion.nombre == arg) else {
+                return Err(argumentos.error(format!("Opción desconocida: {}", arg)));
+            };
+            let valor = match opcion.valor {
+                Some(nombre) => args.next().cloned().ok_or_else(|| {
+                    argumentos.error(format!("La opción {} requiere <{}>", arg, nombre))
+                })?,
+                None => String::new(),
+            };
+            argumentos.opciones.push((opcion.nombre, valor));
+        }
+
+        let (obligatorios, opcionales) = subcomando.posicionales();
+        let recibidos = argumentos.posicionales.len();
+        if let Some(faltante) = obligatorios.get(recibidos) {
+            return Err(argumentos.error(format!("Falta el argumento <{}>", faltante)));
+        }
+        if recibidos > obligatorios.len() + opcionales.len() {
+            let sobrante = &argumentos.posicionales[obligatorios.len() + opcionales.len()];
+            return Err(argumentos.error(format!("Argumento inesperado: {}", sobrante)));
+        }
+        Ok(argumentos)
+    }
+
+    fn error(&self, mensaje: String) -> ErrorUso {
+        ErrorUso {
+            mensaje,
+            subcomando: Some(self.subcomando),
+        }
+    }
+
+    /// Interpreta el valor de una opción o argumento con el tipo esperado.
+    fn valor<T: FromStr>(&self, nombre: &str, valor: &str) -> Result<T, ErrorUso> {
+        valor
+            .parse()
+            .map_err(|_| self.error(format!("Valor inválido para {}: {}", nombre, valor)))
+    }
+
+    /// Interpreta el valor de una opción que debe ser un número mayor a cero.
+    fn positivo(&self, nombre: &str, valor: &str) -> Result<u64, ErrorUso> {
+        match self.valor(nombre, valor)? {
+            0 => Err(self.error(format!("{} debe ser mayor a 0", nombre))),
+            n => Ok(n),
+        }
+    }
+
+    fn comando(self) -> Result<Comando, ErrorUso> {
+        let posicional = |i: usize| self.posicionales.get(i).cloned();
+        let ruta = posicional(0).unwrap_or_default();
+        Ok(match self.subcomando {
+            Subcomando::Run => Comando::Correr {
+                ruta,
+                opciones: self.corrida()?,
+            },
+            Subcomando::Generate => Comando::Generar {
+                generador: self.generador(1)?,
+                ruta,
+            },
+            Subcomando::Validate => Comando::Validar {
+                ruta,
+                formato: self.formato()?,
+            },
+            Subcomando::Simulate => Comando::Simular {
+                generador: self.generador(0)?,
+                opciones: self.corrida()?,
+            },
+            Subcomando::Report => Comando::Reportar {
+                ruta: self.reporte_json(&ruta)?,
+                salida: self
+                    .opciones
+                    .last()
+                    .map(|(_, valor)| self.ruta_reporte(valor))
+                    .transpose()?,
+            },
+            Subcomando::Compare => {
+                let mut umbral = UMBRAL_REGRESION;
+                for (nombre, valor) in &self.opciones {
+                    umbral = self.valor(nombre, valor)?;
+                    if !umbral.is_finite() || umbral < 0.0 {
+                        return Err(self.error(format!("{} debe ser mayor o igual a 0", nombre)));
+                    }
+                }
+                Comando::Comparar {
+                    anterior: self.reporte_json(&ruta)?,
+                    actual: self.reporte_json(&posicional(1).unwrap_or_default())?,
+                    umbral,
+                }
+            }
+        })
+    }
+
+    fn formato(&self) -> Result<Option<FormatoPedidos>, ErrorUso> {
+        let mut formato = None;
+        for (nombre, valor) in &self.opciones {
+            if *nombre == "--format" {
+                formato = Some(self.valor(nombre, valor)?);
+            }
+        }
+        Ok(formato)
+    }
+
+    fn ruta_reporte(&self, ruta: &str) -> Result<String, ErrorUso> {
+        FormatoReporte::desde_ruta(ruta)
+            .map(|_| ruta.to_string())
+            .map_err(|_| self.error(format!("Extensión de reporte no soportada: {}", ruta)))
+    }
+
+    fn reporte_json(&self, ruta: &str) -> Result<String, ErrorUso> {
+        match FormatoReporte::desde_ruta(ruta) {
+            Ok(FormatoReporte::Json) => Ok(ruta.to_string()),
+            _ => Err(self.error(format!("El reporte debe estar en formato JSON: {}", ruta))),
+        }
+    }
+
+    /// Generador con la cantidad del argumento posicional dado y las opciones recibidas.
+    fn generador(&self, cantidad: usize) -> Result<Generador, ErrorUso> {
+        let cantidad = match self.posicionales.get(cantidad) {
+            Some(valor) => self.valor("<cantidad>", valor)?,
+            None => CANT_PEDIDOS,
+        };
+        let mut generador = Generador::new(cantidad);
+        for (nombre, valor) in &self.opciones {
+            match *nombre {
+                "--arrivals" => generador.llegadas = self.valor(nombre, valor)?,
+                "--mix" => generador.mezcla = Some(self.valor(nombre, valor)?),
+                "--seed" => generador.semilla = Some(self.valor(nombre, valor)?),
+                "--format" => generador.formato = Some(self.valor(nombre, valor)?),
+                _ => {}
+            }
+        }
+        Ok(generador)
+    }
+
+    fn corrida(&self) -> Result<OpcionesCorrida, ErrorUso> {
+        let mut opciones = OpcionesCorrida {
+            formato: self.formato()?,
+            ..Default::default()
+        };
+        let configuracion = &mut opciones.configuracion;
+        for (nombre, valor) in &self.opciones {
+            let ruta = Some(valor.clone());
+            match *nombre {
+                "-v" => opciones.nivel = Some(Nivel::Debug),
+                "-q" => opciones.nivel = Some(Nivel::Warn),
+                "--timestamps" => opciones.timestamps = true,
+                "--thread-names" => opciones.hilos = true,
+                "--log-file" => opciones.log = ruta,
+                "-t" => opciones.traza = ruta,
+                "--metrics-addr" => opciones.metricas = ruta,
+                "--report" => opciones.reporte = Some(self.ruta_reporte(valor)?),
+                "--rejected" => opciones.rechazados = ruta,
+                "--strict" => opciones.estricto = true,
+                "--stats-interval" => configuracion.tiempo_stats = self.positivo(nombre, valor)?,
+                "--shutdown" => configuracion.modo_apagado = self.valor(nombre, valor)?,
+                "--pause-refills" => configuracion.pausar_reposicion = true,
+                "--deadline" => configuracion.plazo = Some(self.valor(nombre, valor)?),
+                "--queue-size" => configuracion.largo_cola = Some(self.valor(nombre, valor)?),
+                "--patience" => configuracion.paciencia = Some(self.valor(nombre, valor)?),
+                "--price" => configuracion.precio = self.valor(nombre, valor)?,
+                "--duplicates" => configuracion.repetidos = self.valor(nombre, valor)?,
+                "--retries" => configuracion.reintentos = self.valor(nombre, valor)?,
+                _ => {}
+            }
+        }
+        Ok(opciones)
+    }
+}
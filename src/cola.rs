@@ -0,0 +1,28 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::pedido::Pedido;
+
+/// Clientes que llegaron a la cafetera y esperan que se les asigne un dispensador.
+pub struct ColaClientes {
+    /// Pedidos en orden de llegada junto con su instante de llegada
+    pub pedidos: VecDeque<(Pedido, Instant)>,
+    /// true si no llegarán más clientes
+    pub cerrada: bool,
+}
+
+impl ColaClientes {
+    #[must_use]
+    pub fn new() -> Self {
+        ColaClientes {
+            pedidos: VecDeque::new(),
+            cerrada: false,
+        }
+    }
+}
+
+impl Default for ColaClientes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,162 @@
+use crate::reporte::Reporte;
+
+/// Valor de una métrica en dos corridas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diferencia {
+    pub metrica: String,
+    pub anterior: f64,
+    pub actual: f64,
+    /// true si un valor más alto de la métrica es una mejora
+    pub mayor_es_mejor: bool,
+}
+
+impl Diferencia {
+    fn new(metrica: &str, anterior: f64, actual: f64, mayor_es_mejor: bool) -> Diferencia {
+        Diferencia {
+            metrica: metrica.to_string(),
+            anterior,
+            actual,
+            mayor_es_mejor,
+        }
+    }
+
+    /// Variación porcentual del valor actual respecto del anterior.
+    #[must_use]
+    pub fn variacion(&self) -> f64 {
+        if self.anterior == 0.0 {
+            if self.actual == 0.0 {
+                return 0.0;
+            }
+            return f64::INFINITY.copysign(self.actual);
+        }
+        (self.actual - self.anterior) * 100.0 / self.anterior.abs()
+    }
+
+    /// Devuelve true si la métrica empeoró más que el umbral porcentual dado.
+    #[must_use]
+    pub fn es_regresion(&self, umbral: f64) -> bool {
+        let variacion = self.variacion();
+        if self.mayor_es_mejor {
+            variacion < -umbral
+        } else {
+            variacion > umbral
+        }
+    }
+}
+
+/// Comparación entre los reportes de dos corridas de la misma cafetera.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparacion {
+    pub diferencias: Vec<Diferencia>,
+    /// Variación porcentual a partir de la cual se considera una regresión
+    pub umbral: f64,
+}
+
+impl Comparacion {
+    /// Compara el throughput, los percentiles de latencia, el consumo de granos y leche, y la
+    /// cantidad de reposiciones de ambos reportes.
+    #[must_use]
+    pub fn new(anterior: &Reporte, actual: &Reporte, umbral: f64) -> Comparacion {
+        let mut diferencias = vec![Diferencia::new(
+            "throughput",
+            anterior.throughput(),
+            actual.throughput(),
+            true,
+        )];
+
+        let (a, b) = (&anterior.latencias, &actual.latencias);
+        let latencias = [
+            (
+                "espera_dispensador",
+                a.espera_dispensador,
+                b.espera_dispensador,
+            ),
+            ("espera_cafe", a.espera_cafe, b.espera_cafe),
+            ("espera_espuma", a.espera_espuma, b.espera_espuma),
+            ("total", a.total, b.total),
+        ];
+        for (nombre, a, b) in latencias {
+            if let (Some(a), Some(b)) = (a, b) {
+                for (percentil, va, vb) in [
+                    ("p50", a.p50, b.p50),
+                    ("p95", a.p95, b.p95),
+                    ("p99", a.p99, b.p99),
+                ] {
+                    diferencias.push(Diferencia::new(
+                        &format!("{}_{}_ms", nombre, percentil),
+                        va,
+                        vb,
+                        false,
+                    ));
+                }
+            }
+        }
+
+        let (a, b) = (&anterior.estadisticas, &actual.estadisticas);
+        for (metrica, va, vb) in [
+            (
+                "granos_consumidos",
+                a.granos_consumidos,
+                b.granos_consumidos,
+            ),
+            ("leche_consumida", a.leche_consumida, b.leche_consumida),
+            (
+                "reposiciones_cafe",
+                a.reposiciones_cafe,
+                b.reposiciones_cafe,
+            ),
+            (
+                "reposiciones_espuma",
+                a.reposiciones_espuma,
+                b.reposiciones_espuma,
+            ),
+        ] {
+            diferencias.push(Diferencia::new(metrica, va.into(), vb.into(), false));
+        }
+
+        Comparacion {
+            diferencias,
+            umbral,
+        }
+    }
+
+    /// Métricas que empeoraron más que el umbral.
+    #[must_use]
+    pub fn regresiones(&self) -> Vec<&Diferencia> {
+        self.diferencias
+            .iter()
+            .filter(|d| d.es_regresion(self.umbral))
+            .collect()
+    }
+
+    /// Líneas con el valor anterior, el actual y la variación de cada métrica, marcando las
+    /// regresiones.
+    #[must_use]
+    pub fn lineas(&self) -> Vec<String> {
+        let mut lineas = vec![format!(
+            "{:<28} {:>12} {:>12} {:>10}",
+            "metrica", "anterior", "actual", "variacion"
+        )];
+        for d in &self.diferencias {
+            let marca = if d.es_regresion(self.umbral) {
+                "  <- REGRESION"
+            } else {
+                ""
+            };
+            lineas.push(format!(
+                "{:<28} {:>12.2} {:>12.2} {:>+9.1}%{}",
+                d.metrica,
+                d.anterior,
+                d.actual,
+                d.variacion(),
+                marca
+            ));
+        }
+        lineas.push(format!(
+            "{} regresiones por encima del {}%",
+            self.regresiones().len(),
+            self.umbral
+        ));
+        lineas
+    }
+}
@@ -0,0 +1,101 @@
+use std::str::FromStr;
+
+use crate::constantes::{PRECIO_PEDIDO, TIEMPO_STATS};
+use crate::error::CafeteriaError;
+
+/// Qué hacer con los pedidos en preparación al detener la cafetera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModoApagado {
+    /// Se terminan de preparar
+    Drenar,
+    /// Se interrumpen al finalizar el paso que se está sirviendo
+    Abortar,
+}
+
+impl FromStr for ModoApagado {
+    type Err = CafeteriaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drain" => Ok(ModoApagado::Drenar),
+            "abort" => Ok(ModoApagado::Abortar),
+            _ => Err(CafeteriaError::ArgumentosInvalidos),
+        }
+    }
+}
+
+/// Qué hacer con los pedidos cuyo id ya fue recibido.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoliticaRepetidos {
+    /// Se rechazan como un pedido inválido
+    Rechazar,
+    /// Se preparan con un id nuevo, mayor a todos los recibidos
+    Renombrar,
+    /// Se consideran reenvíos del pedido original, por lo que no se preparan y se informa el
+    /// resultado del original
+    Idempotente,
+}
+
+impl FromStr for PoliticaRepetidos {
+    type Err = CafeteriaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(PoliticaRepetidos::Rechazar),
+            "rename" => Ok(PoliticaRepetidos::Renombrar),
+            "idempotent" => Ok(PoliticaRepetidos::Idempotente),
+            _ => Err(CafeteriaError::ArgumentosInvalidos),
+        }
+    }
+}
+
+/// Parámetros de funcionamiento de una [`Cafetera`](crate::cafetera::Cafetera) que pueden
+/// elegirse al crearla.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Configuracion {
+    /// Tiempo en milisegundos entre cada impresión de las estadísticas
+    pub tiempo_stats: u64,
+    /// Qué hacer con los pedidos en preparación al detener la cafetera
+    pub modo_apagado: ModoApagado,
+    /// true si al pausar la cafetera también se pausan las reposiciones de los contenedores
+    pub pausar_reposicion: bool,
+    /// Plazo en milisegundos de los pedidos que no especifican uno, o `None` para esperar
+    /// indefinidamente
+    pub plazo: Option<u64>,
+    /// Cantidad máxima de clientes en la cola, o `None` para no limitarla. Los clientes que
+    /// llegan con la cola llena se van sin pedir
+    pub largo_cola: Option<usize>,
+    /// Tiempo máximo en milisegundos que un cliente espera en la cola antes de irse, o `None`
+    /// para esperar indefinidamente
+    pub paciencia: Option<u64>,
+    /// Precio de cada pedido
+    pub precio: u32,
+    /// Qué hacer con los pedidos cuyo id ya fue recibido
+    pub repetidos: PoliticaRepetidos,
+    /// Cantidad de veces que se reintenta un pedido cuyo hilo entra en pánico antes de darlo
+    /// por fallido
+    pub reintentos: u32,
+}
+
+impl Configuracion {
+    #[must_use]
+    pub fn new() -> Self {
+        Configuracion {
+            tiempo_stats: TIEMPO_STATS,
+            modo_apagado: ModoApagado::Drenar,
+            pausar_reposicion: false,
+            plazo: None,
+            largo_cola: None,
+            paciencia: None,
+            precio: PRECIO_PEDIDO,
+            repetidos: PoliticaRepetidos::Rechazar,
+            reintentos: 0,
+        }
+    }
+}
+
+impl Default for Configuracion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
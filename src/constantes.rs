@@ -22,5 +22,12 @@ pub const TIEMPO_ESPUMA: u64 = 2000;
 pub const TIEMPO_POR_UNIDAD: u64 = 100;
 /// Tiempo transcurrido hasta mostrar las estadísticas
 pub const TIEMPO_STATS: u64 = 5000;
+/// Tiempo máximo de espera para detectar un pedido de mostrar las estadísticas
+pub const TIEMPO_SENAL: u64 = 100;
 /// Tiempo transcurrido entre cada pedido
 pub const TIEMPO_PEDIDO: u64 = 1000;
+/// Precio de cada pedido, utilizado para estimar los ingresos perdidos
+pub const PRECIO_PEDIDO: u32 = 500;
+
+/// Variación porcentual a partir de la cual se considera una regresión al comparar reportes
+pub const UMBRAL_REGRESION: f64 = 5.0;
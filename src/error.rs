@@ -1,24 +1,251 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::num::{IntErrorKind, ParseIntError};
+use std::sync::Arc;
+
+/// Código de salida del programa ante errores sin un código propio.
+pub const SALIDA_ERROR: u8 = 1;
+/// Código de salida si los argumentos no son válidos.
+pub const SALIDA_ARGUMENTOS: u8 = 2;
+/// Código de salida si no se pudo leer o escribir algún archivo.
+pub const SALIDA_ARCHIVO: u8 = 3;
+/// Código de salida si hay pedidos inválidos, al validar un archivo o en modo estricto.
+pub const SALIDA_PEDIDOS_INVALIDOS: u8 = 4;
+/// Código de salida si algún lock quedó envenenado.
+pub const SALIDA_LOCK_ENVENENADO: u8 = 5;
+/// Código de salida si la corrida finalizó sin servir todos los pedidos.
+pub const SALIDA_CORRIDA_PARCIAL: u8 = 6;
+
 /// Errores posibles del programa.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CafeteriaError {
     /// No se recibió la ruta al archivo con pedidos.
     ArgumentosInvalidos,
     /// No se pudo convertir una línea del archivo de pedidos a un [Pedido](`crate::pedido::Pedido`).
-    PedidoInvalido,
+    PedidoInvalido(DetallePedido),
     /// No se pudo abrir el archivo de pedidos.
-    CreacionArchivo,
+    CreacionArchivo(ErrorIo),
     /// No se pudo abrir el archivo de pedidos.
-    AperturaArchivo,
+    AperturaArchivo(ErrorIo),
     /// No se pudo escribir en el archivo de pedidos.
-    EscrituraArchivo,
+    EscrituraArchivo(ErrorIo),
     /// No se pudo leer una línea del archivo de pedidos.
-    LecturaArchivo,
+    LecturaArchivo(ErrorIo),
     LockEnvenenado,
+    /// No se pudo iniciar el servidor de métricas en la dirección dada.
+    ServidorMetricas(ErrorIo),
+    /// La extensión del archivo no corresponde a ningún formato soportado.
+    FormatoInvalido,
+    /// No se pudo crear un hilo.
+    CreacionHilo(ErrorIo),
+    /// No se pudo registrar el manejador de una señal.
+    Senal(ErrorIo),
+}
+
+impl CafeteriaError {
+    /// Código con el que debe finalizar el programa a causa de este error.
+    #[must_use]
+    pub fn codigo_salida(&self) -> u8 {
+        match self {
+            CafeteriaError::ArgumentosInvalidos => SALIDA_ARGUMENTOS,
+            CafeteriaError::PedidoInvalido(_) => SALIDA_PEDIDOS_INVALIDOS,
+            CafeteriaError::CreacionArchivo(_)
+            | CafeteriaError::AperturaArchivo(_)
+            | CafeteriaError::EscrituraArchivo(_)
+            | CafeteriaError::LecturaArchivo(_)
+            | CafeteriaError::FormatoInvalido => SALIDA_ARCHIVO,
+            CafeteriaError::LockEnvenenado => SALIDA_LOCK_ENVENENADO,
+            CafeteriaError::ServidorMetricas(_)
+            | CafeteriaError::CreacionHilo(_)
+            | CafeteriaError::Senal(_) => SALIDA_ERROR,
+        }
+    }
+
+    /// Descripción del error seguida de la de cada una de sus causas, separadas por `": "`.
+    #[must_use]
+    pub fn descripcion(&self) -> String {
+        let mut descripcion = self.to_string();
+        let mut causa = self.source();
+        while let Some(error) = causa {
+            descripcion.push_str(": ");
+            descripcion.push_str(&error.to_string());
+            causa = error.source();
+        }
+        descripcion
+    }
+
+    /// Indica el número de línea del pedido inválido, si el error es un
+    /// [`CafeteriaError::PedidoInvalido`].
+    #[must_use]
+    pub fn en_linea(self, linea: usize) -> Self {
+        match self {
+            CafeteriaError::PedidoInvalido(detalle) => {
+                CafeteriaError::PedidoInvalido(DetallePedido {
+                    linea: Some(linea),
+                    ..detalle
+                })
+            }
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for CafeteriaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CafeteriaError::ArgumentosInvalidos => write!(f, "argumentos invalidos"),
+            CafeteriaError::PedidoInvalido(_) => write!(f, "pedido invalido"),
+            CafeteriaError::CreacionArchivo(_) => write!(f, "no se pudo crear el archivo"),
+            CafeteriaError::AperturaArchivo(_) => write!(f, "no se pudo abrir el archivo"),
+            CafeteriaError::EscrituraArchivo(_) => write!(f, "no se pudo escribir el archivo"),
+            CafeteriaError::LecturaArchivo(_) => write!(f, "no se pudo leer el archivo"),
+            CafeteriaError::LockEnvenenado => write!(f, "lock envenenado"),
+            CafeteriaError::ServidorMetricas(_) => {
+                write!(f, "no se pudo iniciar el servidor de metricas")
+            }
+            CafeteriaError::FormatoInvalido => write!(f, "formato de archivo invalido"),
+            CafeteriaError::CreacionHilo(_) => write!(f, "no se pudo crear un hilo"),
+            CafeteriaError::Senal(_) => write!(f, "no se pudo registrar el manejador de una senal"),
+        }
+    }
+}
+
+impl Error for CafeteriaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CafeteriaError::PedidoInvalido(detalle) => Some(detalle),
+            CafeteriaError::CreacionArchivo(causa)
+            | CafeteriaError::AperturaArchivo(causa)
+            | CafeteriaError::EscrituraArchivo(causa)
+            | CafeteriaError::LecturaArchivo(causa)
+            | CafeteriaError::ServidorMetricas(causa)
+            | CafeteriaError::CreacionHilo(causa)
+            | CafeteriaError::Senal(causa) => Some(causa.0.as_ref()),
+            CafeteriaError::ArgumentosInvalidos
+            | CafeteriaError::LockEnvenenado
+            | CafeteriaError::FormatoInvalido => None,
+        }
+    }
+}
+
+/// Error de entrada/salida que causó un [`CafeteriaError`]. Dos errores se consideran iguales
+/// si son del mismo [`io::ErrorKind`], para poder comparar los [`CafeteriaError`] por su
+/// variante.
+#[derive(Debug, Clone)]
+pub struct ErrorIo(Arc<io::Error>);
+
+impl ErrorIo {
+    #[must_use]
+    pub fn tipo(&self) -> io::ErrorKind {
+        self.0.kind()
+    }
+}
+
+impl From<io::Error> for ErrorIo {
+    fn from(error: io::Error) -> Self {
+        ErrorIo(Arc::new(error))
+    }
+}
+
+impl From<serde_json::Error> for ErrorIo {
+    fn from(error: serde_json::Error) -> Self {
+        io::Error::from(error).into()
+    }
+}
+
+impl PartialEq for ErrorIo {
+    fn eq(&self, other: &Self) -> bool {
+        self.tipo() == other.tipo()
+    }
+}
+
+impl Eq for ErrorIo {}
+
+impl fmt::Display for ErrorIo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Motivo por el que un pedido es inválido.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Motivo {
+    /// La cantidad no está en el rango [[`MIN_CANTIDAD`](crate::constantes::MIN_CANTIDAD),
+    /// [`MAX_CANTIDAD`](crate::constantes::MAX_CANTIDAD)], o el número es demasiado grande
+    FueraDeRango,
+    /// El campo no es un número
+    NoNumerico,
+    /// Falta un campo obligatorio o está vacío
+    CampoFaltante,
+    /// La línea tiene más campos que los esperados
+    CamposDeMas,
+    /// La bebida no está en [`BEBIDAS`](crate::bebidas::BEBIDAS)
+    BebidaDesconocida,
+    /// Ya se recibió un pedido con el mismo id
+    IdRepetido,
+    /// El pedido JSON o YAML no tiene la estructura esperada, con la descripción del error
+    Estructura(String),
+}
+
+/// Detalle de un pedido inválido.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetallePedido {
+    /// Número de línea en el archivo, o posición del pedido en los formatos JSON y YAML,
+    /// comenzando en 1
+    pub linea: Option<usize>,
+    /// Campo inválido, si se puede identificar
+    pub campo: Option<&'static str>,
+    pub motivo: Motivo,
+}
+
+impl DetallePedido {
+    #[must_use]
+    pub fn new(campo: Option<&'static str>, motivo: Motivo) -> Self {
+        DetallePedido {
+            linea: None,
+            campo,
+            motivo,
+        }
+    }
+}
+
+impl fmt::Display for DetallePedido {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(linea) = self.linea {
+            write!(f, "linea {}: ", linea)?;
+        }
+        if let Some(campo) = self.campo {
+            write!(f, "campo {}: ", campo)?;
+        }
+        match &self.motivo {
+            Motivo::FueraDeRango => write!(f, "fuera de rango"),
+            Motivo::NoNumerico => write!(f, "no es un numero"),
+            Motivo::CampoFaltante => write!(f, "falta el campo"),
+            Motivo::CamposDeMas => write!(f, "tiene campos de mas"),
+            Motivo::BebidaDesconocida => write!(f, "bebida desconocida"),
+            Motivo::IdRepetido => write!(f, "id repetido"),
+            Motivo::Estructura(descripcion) => write!(f, "{}", descripcion),
+        }
+    }
+}
+
+impl Error for DetallePedido {}
+
+impl From<DetallePedido> for CafeteriaError {
+    fn from(detalle: DetallePedido) -> Self {
+        CafeteriaError::PedidoInvalido(detalle)
+    }
 }
 
-impl From<std::num::ParseIntError> for CafeteriaError {
-    fn from(_: std::num::ParseIntError) -> Self {
-        CafeteriaError::PedidoInvalido
+impl From<ParseIntError> for CafeteriaError {
+    fn from(error: ParseIntError) -> Self {
+        let motivo = match error.kind() {
+            IntErrorKind::Empty => Motivo::CampoFaltante,
+            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => Motivo::FueraDeRango,
+            _ => Motivo::NoNumerico,
+        };
+        DetallePedido::new(None, motivo).into()
     }
 }
 
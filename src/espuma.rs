@@ -1,10 +1,12 @@
 use std::cmp::min;
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::constantes::{E, L, MAX_CANTIDAD, TIEMPO_ESPUMA};
-use crate::error::CafeteriaError;
+use crate::recuperacion::{acotar, bloquear, recuperar, Reparable};
+use crate::traza::{Pista, Traza};
+use crate::{debug, info};
 
 pub struct ContenedorEspuma {
     /// Cantidad actual de espuma
@@ -13,10 +15,18 @@ pub struct ContenedorEspuma {
     pub leche: u32,
     /// true si no quedan pedidos por realizar
     pub fin: bool,
+    /// true si las reposiciones se encuentran pausadas
+    pub pausado: bool,
     /// Cantidad total de espuma consumida
     pub espuma_consumida: u32,
     /// Cantidad total de leche consumida
     pub leche_consumida: u32,
+    /// Cantidad de reposiciones de espuma realizadas
+    pub reposiciones: u32,
+    /// Tiempo total durante el cual se estuvo reponiendo espuma
+    pub tiempo_reposicion: Duration,
+    /// Cantidad de veces que se recuperó el lock del contenedor tras quedar envenenado
+    pub incidentes: u32,
 }
 
 impl ContenedorEspuma {
@@ -26,12 +36,30 @@ impl ContenedorEspuma {
             espuma: 0,
             leche: L,
             fin: false,
+            pausado: false,
             espuma_consumida: 0,
             leche_consumida: 0,
+            reposiciones: 0,
+            tiempo_reposicion: Duration::ZERO,
+            incidentes: 0,
         }
     }
 }
 
+impl Reparable for ContenedorEspuma {
+    /// Acota la cantidad de espuma a [`E`] y la de leche a [`L`].
+    fn reparar(&mut self) -> Vec<String> {
+        self.incidentes += 1;
+        [
+            acotar(&mut self.espuma, E, "espuma"),
+            acotar(&mut self.leche, L, "leche"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
 impl Default for ContenedorEspuma {
     fn default() -> Self {
         Self::new()
@@ -42,36 +70,43 @@ impl Default for ContenedorEspuma {
 /// y el contenedor se encuentre disponible. Se rellena por completo en [`TIEMPO_ESPUMA`] milisegundos,
 /// durante este tiempo no se podrá utilizar el dispensador de espuma.
 /// También se rellena el contenedor de leche cuando su cantidad sea menor a [`E`], esto es instantáneo.
+/// Cada reposición queda registrada en la [`Traza`] recibida.
+/// No se repone mientras [`ContenedorEspuma`].pausado sea true.
 /// Finaliza cuando [`ContenedorEspuma`].fin es true.
-///
-/// # Errors
-/// * En caso de que el lock del contenedor se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
-pub fn rellenar_espuma(
-    contenedor: Arc<(Mutex<ContenedorEspuma>, Condvar)>,
-) -> Result<(), CafeteriaError> {
+/// Si el lock del contenedor queda envenenado, se recupera mediante [`recuperar`].
+pub fn rellenar_espuma(contenedor: Arc<(Mutex<ContenedorEspuma>, Condvar)>, traza: Arc<Traza>) {
     let (espuma_lock, espuma_cvar) = &*contenedor;
     loop {
-        if let Ok(mut state) = espuma_cvar.wait_while(espuma_lock.lock()?, |cont| {
-            cont.espuma >= MAX_CANTIDAD && !cont.fin
-        }) {
-            if state.fin {
-                break;
-            }
-            println!("[DEBUG] Reponiendo espuma");
-            thread::sleep(Duration::from_millis(TIEMPO_ESPUMA));
-            let cantidad = min(E - state.espuma, state.leche);
-            state.espuma += cantidad;
-            state.leche -= cantidad;
-            state.leche_consumida += cantidad;
-            if state.leche < E {
-                println!(
-                    "[INFO] Contenedor de leche por debajo del {}%. Reponiendo.",
-                    E * 100 / L
-                );
-                state.leche = L;
-            }
-            espuma_cvar.notify_one();
+        let mut state = recuperar(
+            espuma_lock,
+            "espuma",
+            espuma_cvar.wait_while(bloquear(espuma_lock, "espuma"), |cont| {
+                (cont.espuma >= MAX_CANTIDAD || cont.pausado) && !cont.fin
+            }),
+        );
+        if state.fin {
+            break;
+        }
+        debug!("Reponiendo espuma");
+        let inicio = Instant::now();
+        thread::sleep(Duration::from_millis(TIEMPO_ESPUMA));
+        let cantidad = min(E - state.espuma, state.leche);
+        state.espuma += cantidad;
+        state.leche -= cantidad;
+        state.leche_consumida += cantidad;
+        if state.leche < E {
+            info!(
+                "Contenedor de leche por debajo del {}%. Reponiendo.",
+                E * 100 / L
+            );
+            state.leche = L;
         }
+        let fin = Instant::now();
+        state.reposiciones += 1;
+        state.tiempo_reposicion += fin.saturating_duration_since(inicio);
+        traza.intervalo(Pista::RellenoEspuma, "reposicion", None, inicio, fin);
+        traza.contador("espuma", state.espuma);
+        traza.contador("leche", state.leche);
+        espuma_cvar.notify_one();
     }
-    Ok(())
 }
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+
+/// Estado de un dispensador.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstadoDispensador {
+    Libre,
+    /// Preparando un pedido
+    Ocupado,
+}
+
+/// Cantidades servidas a pedidos que no se completaron.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Desperdicio {
+    pub agua: u32,
+    pub cafe: u32,
+    pub espuma: u32,
+}
+
+impl Desperdicio {
+    /// Suma al desperdicio las cantidades del desperdicio dado.
+    pub fn agregar(&mut self, otro: Desperdicio) {
+        self.agua += otro.agua;
+        self.cafe += otro.cafe;
+        self.espuma += otro.espuma;
+    }
+}
+
+/// Estado de la cafetera en un instante dado.
+///
+/// Se obtiene mediante [`Cafetera::snapshot`](crate::cafetera::Cafetera::snapshot), que toma todos
+/// los locks a la vez, por lo que todos sus valores son consistentes entre sí.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Estadisticas {
+    /// Cantidad actual de cafe molido
+    pub cafe_molido: u32,
+    /// Cantidad actual de granos de cafe
+    pub granos: u32,
+    /// Cantidad actual de espuma
+    pub espuma: u32,
+    /// Cantidad actual de leche
+    pub leche: u32,
+    /// Cantidad total de cafe molido consumido
+    pub cafe_consumido: u32,
+    /// Cantidad total de granos de cafe consumidos
+    pub granos_consumidos: u32,
+    /// Cantidad total de espuma consumida
+    pub espuma_consumida: u32,
+    /// Cantidad total de leche consumida
+    pub leche_consumida: u32,
+    /// Cantidad de reposiciones de cafe molido realizadas
+    pub reposiciones_cafe: u32,
+    /// Cantidad de reposiciones de espuma realizadas
+    pub reposiciones_espuma: u32,
+    /// Estado de cada dispensador
+    pub dispensadores: Vec<EstadoDispensador>,
+    /// Cantidad total de pedidos completados
+    pub cant_pedidos: u32,
+    /// Cantidad de líneas leídas del archivo de pedidos
+    pub pedidos_leidos: u32,
+    /// Cantidad de líneas del archivo de pedidos que no pudieron procesarse
+    pub pedidos_rechazados: u32,
+    /// Cantidad de pedidos recibidos con un id ya recibido
+    #[serde(default)]
+    pub pedidos_repetidos: u32,
+    /// Cantidad de pedidos interrumpidos al detener la cafetera
+    #[serde(default)]
+    pub pedidos_abortados: u32,
+    /// Cantidad de pedidos cuyo hilo entró en pánico y agotaron sus reintentos
+    #[serde(default)]
+    pub pedidos_fallidos: u32,
+    /// Cantidad de pedidos cancelados, tanto antes de llegar a un dispensador como durante su
+    /// preparación
+    #[serde(default)]
+    pub pedidos_cancelados: u32,
+    /// Cantidad de pedidos abandonados por superar su plazo
+    #[serde(default)]
+    pub pedidos_vencidos: u32,
+    /// Cantidad de clientes que se fueron sin pedir porque la cola estaba llena
+    #[serde(default)]
+    pub clientes_desistidos: u32,
+    /// Cantidad de clientes que se fueron de la cola por superar su paciencia
+    #[serde(default)]
+    pub clientes_abandonados: u32,
+    /// Ingresos estimados de los clientes perdidos
+    #[serde(default)]
+    pub ingresos_perdidos: u64,
+    /// Cantidades servidas a pedidos abortados, cancelados o abandonados
+    #[serde(default)]
+    pub desperdicio: Desperdicio,
+    /// Cantidad de clientes esperando que se les asigne un dispensador
+    #[serde(default)]
+    pub clientes_en_cola: u32,
+    /// true si la cafetera se encuentra pausada
+    #[serde(default)]
+    pub pausada: bool,
+    /// Tiempo total en milisegundos que la cafetera estuvo pausada
+    #[serde(default)]
+    pub tiempo_pausado_ms: u64,
+    /// Cantidad de veces que se recuperó el lock de un contenedor tras quedar envenenado
+    #[serde(default)]
+    pub locks_recuperados: u32,
+}
+
+impl Estadisticas {
+    /// Cantidad de clientes que se fueron sin su pedido: los que desistieron, abandonaron la
+    /// cola o superaron su plazo.
+    #[must_use]
+    pub fn clientes_perdidos(&self) -> u32 {
+        self.clientes_desistidos + self.clientes_abandonados + self.pedidos_vencidos
+    }
+
+    /// Devuelve true si algún pedido leído no se sirvió por un motivo distinto de su cancelación:
+    /// se abortó, falló, venció su plazo o el cliente desistió o abandonó la cola.
+    #[must_use]
+    pub fn es_parcial(&self) -> bool {
+        self.pedidos_abortados + self.pedidos_fallidos + self.clientes_perdidos() > 0
+    }
+
+    /// Porcentaje de los pedidos finalizados que se abandonaron por superar su plazo.
+    #[must_use]
+    pub fn tasa_abandono(&self) -> f64 {
+        let finalizados = self.cant_pedidos
+            + self.pedidos_abortados
+            + self.pedidos_fallidos
+            + self.pedidos_cancelados
+            + self.pedidos_vencidos;
+        if finalizados == 0 {
+            return 0.0;
+        }
+        f64::from(self.pedidos_vencidos) * 100.0 / f64::from(finalizados)
+    }
+
+    /// Líneas con el estado de los contenedores, el consumo total y los pedidos completados.
+    #[must_use]
+    pub fn lineas(&self) -> Vec<String> {
+        let ocupados = self
+            .dispensadores
+            .iter()
+            .filter(|&&d| d == EstadoDispensador::Ocupado)
+            .count();
+        vec![
+            format!(
+                "Estado contenedores: cafe {}, granos {}, espuma {}, leche {}",
+                self.cafe_molido, self.granos, self.espuma, self.leche
+            ),
+            format!(
+                "Consumo total: cafe {}, granos {}, espuma {}, leche {}",
+                self.cafe_consumido,
+                self.granos_consumidos,
+                self.espuma_consumida,
+                self.leche_consumida
+            ),
+            format!(
+                "Dispensadores ocupados: {}/{}, clientes en cola: {}",
+                ocupados,
+                self.dispensadores.len(),
+                self.clientes_en_cola
+            ),
+            format!(
+                "Pedidos leidos: {}, rechazados: {}, repetidos: {}, completados: {}, abortados: {}, fallidos: {}, cancelados: {}",
+                self.pedidos_leidos,
+                self.pedidos_rechazados,
+                self.pedidos_repetidos,
+                self.cant_pedidos,
+                self.pedidos_abortados,
+                self.pedidos_fallidos,
+                self.pedidos_cancelados
+            ),
+            format!(
+                "Pedidos abandonados por plazo: {} ({:.1}%)",
+                self.pedidos_vencidos,
+                self.tasa_abandono()
+            ),
+            format!(
+                "Clientes perdidos: {} (desistieron {}, abandonaron la cola {}), ingresos perdidos: {}",
+                self.clientes_perdidos(),
+                self.clientes_desistidos,
+                self.clientes_abandonados,
+                self.ingresos_perdidos
+            ),
+            format!(
+                "Desperdicio: agua {}, cafe {}, espuma {}",
+                self.desperdicio.agua, self.desperdicio.cafe, self.desperdicio.espuma
+            ),
+            format!(
+                "Pausada: {}, tiempo pausado: {} ms",
+                if self.pausada { "si" } else { "no" },
+                self.tiempo_pausado_ms
+            ),
+            format!("Locks recuperados: {}", self.locks_recuperados),
+        ]
+    }
+}
@@ -0,0 +1,276 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs::File;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::bebidas::Bebida;
+use crate::error::CafeteriaError;
+use crate::info;
+use crate::lectura::{Entrada, Entradas, FormatoPedidos};
+use crate::pedido::{Linea, Pedido};
+
+/// Tramo de un perfil de llegadas durante el cual los clientes llegan con una tasa constante.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tramo {
+    /// Clientes por minuto
+    pub tasa: f64,
+    /// Duración del tramo en milisegundos
+    pub duracion: u64,
+}
+
+/// Forma en la que se distribuyen las llegadas de los clientes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModeloLlegadas {
+    /// Sin momento de llegada: la cafetera los recibe a intervalos fijos
+    Fijo,
+    /// Proceso de Poisson con la tasa dada en clientes por minuto
+    Poisson(f64),
+    /// Grupos del tamaño dado que llegan a la vez, como en la hora pico de la mañana. Los grupos
+    /// llegan según un proceso de Poisson de forma que la tasa media de clientes por minuto sea
+    /// la dada
+    Rafagas { tasa: f64, tamano: usize },
+    /// Proceso de Poisson cuya tasa cambia a lo largo del día según los tramos dados. Luego del
+    /// último tramo se mantiene su tasa
+    Perfil(Vec<Tramo>),
+}
+
+impl FromStr for ModeloLlegadas {
+    type Err = CafeteriaError;
+
+    /// Parsea `fixed`, `poisson:<tasa>`, `rush:<tasa>:<tamano>` o
+    /// `profile:<tasa>@<duracion>,<tasa>@<duracion>,...`, con tasas en clientes por minuto y
+    /// duraciones en milisegundos.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tasa = |valor: &str| match valor.parse::<f64>() {
+            Ok(tasa) if tasa > 0.0 && tasa.is_finite() => Ok(tasa),
+            _ => Err(CafeteriaError::ArgumentosInvalidos),
+        };
+        let mut partes = s.trim().splitn(2, ':');
+        match (partes.next(), partes.next()) {
+            (Some("fixed"), None) => Ok(ModeloLlegadas::Fijo),
+            (Some("poisson"), Some(valor)) => Ok(ModeloLlegadas::Poisson(tasa(valor)?)),
+            (Some("rush"), Some(valores)) => {
+                let (valor, tamano) = valores
+                    .split_once(':')
+                    .ok_or(CafeteriaError::ArgumentosInvalidos)?;
+                let tamano = tamano
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&tamano| tamano > 0)
+                    .ok_or(CafeteriaError::ArgumentosInvalidos)?;
+                Ok(ModeloLlegadas::Rafagas {
+                    tasa: tasa(valor)?,
+                    tamano,
+                })
+            }
+            (Some("profile"), Some(tramos)) => tramos
+                .split(',')
+                .map(|tramo| {
+                    let (valor, duracion) = tramo
+                        .split_once('@')
+                        .ok_or(CafeteriaError::ArgumentosInvalidos)?;
+                    Ok(Tramo {
+                        tasa: tasa(valor)?,
+                        duracion: duracion
+                            .parse()
+                            .map_err(|_| CafeteriaError::ArgumentosInvalidos)?,
+                    })
+                })
+                .collect::<Result<_, _>>()
+                .map(ModeloLlegadas::Perfil),
+            _ => Err(CafeteriaError::ArgumentosInvalidos),
+        }
+    }
+}
+
+/// Proporción de cada bebida entre los pedidos generados.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mezcla(pub Vec<(Bebida, u32)>);
+
+impl FromStr for Mezcla {
+    type Err = CafeteriaError;
+
+    /// Parsea `<bebida>=<peso>,<bebida>=<peso>,...`, por ejemplo `latte=40,espresso=60`. Los
+    /// pesos son relativos entre sí, y su suma debe ser mayor a 0 y no superar [`u32::MAX`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mezcla = s
+            .split(',')
+            .map(|parte| {
+                let (nombre, peso) = parte
+                    .split_once('=')
+                    .ok_or(CafeteriaError::ArgumentosInvalidos)?;
+                let peso = peso
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| CafeteriaError::ArgumentosInvalidos)?;
+                Ok((Bebida::buscar(nombre)?, peso))
+            })
+            .collect::<Result<Vec<_>, CafeteriaError>>()?;
+        let total = mezcla
+            .iter()
+            .try_fold(0u32, |total, &(_, peso)| total.checked_add(peso));
+        if total.is_none_or(|total| total == 0) {
+            return Err(CafeteriaError::ArgumentosInvalidos);
+        }
+        Ok(Mezcla(mezcla))
+    }
+}
+
+impl Mezcla {
+    /// Elige una bebida al azar según los pesos de la mezcla.
+    fn elegir(&self, rng: &mut StdRng) -> Bebida {
+        // Se suma en u64 para no desbordar con una mezcla construida sin parsear
+        let total: u64 = self.0.iter().map(|&(_, peso)| u64::from(peso)).sum();
+        let mut valor = rng.gen_range(0..total.max(1));
+        for &(bebida, peso) in &self.0 {
+            if valor < u64::from(peso) {
+                return bebida;
+            }
+            valor -= u64::from(peso);
+        }
+        self.0[self.0.len() - 1].0
+    }
+}
+
+/// Generador de archivos de pedidos aleatorios.
+#[derive(Debug, Clone)]
+pub struct Generador {
+    /// Cantidad de pedidos a generar
+    pub cantidad: usize,
+    pub llegadas: ModeloLlegadas,
+    /// Bebidas a pedir, o `None` para cantidades uniformemente aleatorias
+    pub mezcla: Option<Mezcla>,
+    /// Semilla del generador de números aleatorios, o `None` para elegir una al azar
+    pub semilla: Option<u64>,
+    /// Formato del archivo a escribir, o `None` para elegirlo según la extensión de la ruta
+    pub formato: Option<FormatoPedidos>,
+}
+
+impl Generador {
+    /// Crea un generador de la cantidad de pedidos dada, con cantidades uniformemente aleatorias
+    /// y sin momento de llegada.
+    #[must_use]
+    pub fn new(cantidad: usize) -> Self {
+        Generador {
+            cantidad,
+            llegadas: ModeloLlegadas::Fijo,
+            mezcla: None,
+            semilla: None,
+            formato: None,
+        }
+    }
+
+    /// Genera los pedidos. Con la misma semilla y parámetros siempre se obtienen los mismos
+    /// pedidos.
+    #[must_use]
+    pub fn generar(&self) -> Vec<Pedido> {
+        let semilla = self.semilla.unwrap_or_else(|| rand::thread_rng().gen());
+        info!("Generando pedidos con semilla {}", semilla);
+        let mut rng = StdRng::seed_from_u64(semilla);
+        let llegadas = self.llegadas(&mut rng);
+        (1..=self.cantidad)
+            .zip(llegadas)
+            .map(|(id, llegada)| {
+                let mut pedido = match &self.mezcla {
+                    Some(mezcla) => {
+                        let bebida = mezcla.elegir(&mut rng);
+                        Pedido {
+                            id,
+                            agua: bebida.agua,
+                            cafe: bebida.cafe,
+                            espuma: bebida.espuma,
+                            bebida: Some(bebida.nombre.to_string()),
+                            ..Default::default()
+                        }
+                    }
+                    None => Pedido::aleatorio(id, &mut rng),
+                };
+                pedido.llegada = llegada;
+                pedido
+            })
+            .collect()
+    }
+
+    /// Genera los pedidos como entradas de un archivo, para realizarlos sin escribirlo.
+    #[must_use]
+    pub fn entradas(&self) -> Entradas {
+        Box::new(self.generar().into_iter().map(|pedido| Entrada {
+            texto: pedido.to_line(),
+            resultado: Ok(Linea::Pedido(pedido)),
+        }))
+    }
+
+    /// Escribe los pedidos generados en la ruta dada, en el formato indicado o, si no se indicó
+    /// ninguno, en el que corresponde a la extensión de la ruta.
+    ///
+    /// # Errors
+    /// * En caso de error al crear el archivo, devuelve [`CafeteriaError::CreacionArchivo`].
+    /// * En caso de error al escribir el archivo, devuelve [`CafeteriaError::EscrituraArchivo`].
+    pub fn escribir(&self, ruta: &str) -> Result<(), CafeteriaError> {
+        let formato = self
+            .formato
+            .unwrap_or_else(|| FormatoPedidos::desde_ruta(ruta));
+        let mut file = File::create(ruta).map_err(|e| CafeteriaError::CreacionArchivo(e.into()))?;
+        let pedidos = self.generar();
+        match formato {
+            FormatoPedidos::Texto => pedidos
+                .iter()
+                .try_for_each(|pedido| writeln!(file, "{}", pedido.to_line())),
+            FormatoPedidos::JsonLines => pedidos.iter().try_for_each(|pedido| {
+                serde_json::to_writer(&mut file, pedido)?;
+                writeln!(file)
+            }),
+            FormatoPedidos::Json => {
+                serde_json::to_writer_pretty(&mut file, &pedidos).map_err(Into::into)
+            }
+            FormatoPedidos::Yaml => {
+                serde_yaml::to_writer(&mut file, &pedidos).map_err(std::io::Error::other)
+            }
+        }
+        .map_err(|e| CafeteriaError::EscrituraArchivo(e.into()))
+    }
+
+    /// Momentos de llegada en milisegundos de cada pedido según el modelo de llegadas.
+    fn llegadas(&self, rng: &mut StdRng) -> Vec<Option<u64>> {
+        let tramos = match &self.llegadas {
+            ModeloLlegadas::Fijo => return vec![None; self.cantidad],
+            ModeloLlegadas::Poisson(tasa) => vec![Tramo {
+                tasa: *tasa,
+                duracion: u64::MAX,
+            }],
+            ModeloLlegadas::Rafagas { tasa, tamano } => {
+                let grupos = Generador {
+                    cantidad: self.cantidad.div_ceil(*tamano),
+                    llegadas: ModeloLlegadas::Poisson(tasa / *tamano as f64),
+                    ..self.clone()
+                }
+                .llegadas(rng);
+                return grupos
+                    .into_iter()
+                    .flat_map(|llegada| vec![llegada; *tamano])
+                    .take(self.cantidad)
+                    .collect();
+            }
+            ModeloLlegadas::Perfil(tramos) => tramos.clone(),
+        };
+
+        // Por la falta de memoria de la distribución exponencial, al cruzar el fin de un tramo
+        // se puede volver a sortear desde ese punto con la tasa del tramo siguiente
+        let mut llegadas = Vec::with_capacity(self.cantidad);
+        let (mut tiempo, mut tramo, mut fin_tramo) = (0.0, 0, tramos[0].duracion as f64);
+        while llegadas.len() < self.cantidad {
+            let tasa = tramos[tramo].tasa / 60_000.0;
+            let intervalo = -(1.0 - rng.gen::<f64>()).ln() / tasa;
+            if tiempo + intervalo > fin_tramo && tramo + 1 < tramos.len() {
+                tiempo = fin_tramo;
+                tramo += 1;
+                fin_tramo += tramos[tramo].duracion as f64;
+                continue;
+            }
+            tiempo += intervalo;
+            llegadas.push(Some(tiempo as u64));
+        }
+        llegadas
+    }
+}
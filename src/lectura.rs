@@ -0,0 +1,422 @@
+use serde::Serialize;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::bebidas::Bebida;
+use crate::error::{CafeteriaError, DetallePedido, Motivo};
+use crate::pedido::{campo, Linea, Pedido, CANCELACION};
+
+/// Entrada de un archivo de pedidos.
+#[derive(Debug)]
+pub struct Entrada {
+    /// Contenido original de la entrada: la línea en los formatos de texto y JSON Lines, o el
+    /// pedido como JSON en los formatos JSON y YAML
+    pub texto: String,
+    /// Pedido o cancelación, o el error si no pudo procesarse
+    pub resultado: Result<Linea, CafeteriaError>,
+}
+
+/// Entradas de un archivo de pedidos.
+pub type Entradas = Box<dyn Iterator<Item = Entrada> + Send>;
+
+/// Entrada de un archivo de pedidos que no pudo procesarse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rechazo {
+    /// Contenido original de la entrada, ver [`Entrada::texto`]
+    pub texto: String,
+    pub error: CafeteriaError,
+}
+
+impl Rechazo {
+    /// Descripción del error para las personas que deban corregir la entrada.
+    #[must_use]
+    pub fn descripcion(&self) -> String {
+        match &self.error {
+            CafeteriaError::PedidoInvalido(detalle) => detalle.to_string(),
+            error => error.descripcion(),
+        }
+    }
+}
+
+/// Escribe las entradas rechazadas en la ruta dada, cada una precedida por un comentario con
+/// su error, de forma que puedan corregirse y volver a procesarse.
+///
+/// # Errors
+/// * En caso de error al crear el archivo, devuelve [`CafeteriaError::CreacionArchivo`].
+/// * En caso de error al escribir el archivo, devuelve [`CafeteriaError::EscrituraArchivo`].
+pub fn escribir_rechazados(ruta: &str, rechazados: &[Rechazo]) -> Result<(), CafeteriaError> {
+    let mut file = File::create(ruta).map_err(|e| CafeteriaError::CreacionArchivo(e.into()))?;
+    for rechazo in rechazados {
+        writeln!(file, "{} {}", COMENTARIO, rechazo.descripcion())
+            .and_then(|_| writeln!(file, "{}", rechazo.texto))
+            .map_err(|e| CafeteriaError::EscrituraArchivo(e.into()))?;
+    }
+    Ok(())
+}
+
+/// Formato de un archivo de pedidos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatoPedidos {
+    /// Una línea por pedido, ver [`LectorPedidos`]
+    Texto,
+    /// Arreglo JSON de pedidos
+    Json,
+    /// Un objeto JSON por línea
+    JsonLines,
+    /// Secuencia YAML de pedidos
+    Yaml,
+}
+
+impl FormatoPedidos {
+    /// Elige el formato según la extensión de la ruta: `.json`, `.jsonl` o `.ndjson`, `.yaml`
+    /// o `.yml`, y texto en cualquier otro caso.
+    #[must_use]
+    pub fn desde_ruta(ruta: &str) -> FormatoPedidos {
+        let extension = Path::new(ruta)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("json") => FormatoPedidos::Json,
+            Some("jsonl" | "ndjson") => FormatoPedidos::JsonLines,
+            Some("yaml" | "yml") => FormatoPedidos::Yaml,
+            _ => FormatoPedidos::Texto,
+        }
+    }
+
+    /// Lee las entradas del archivo en este formato. Los archivos de texto y JSON Lines se leen
+    /// a medida que se consumen las entradas, mientras que los JSON y YAML se leen por completo.
+    /// Los errores de los pedidos inválidos indican su número de línea, o su posición en el
+    /// arreglo en los formatos JSON y YAML.
+    ///
+    /// # Errors
+    /// * En caso de que un archivo JSON o YAML no contenga un arreglo, devuelve [`CafeteriaError::FormatoInvalido`].
+    pub fn entradas<R: BufRead + Send + 'static>(
+        self,
+        archivo: R,
+    ) -> Result<Entradas, CafeteriaError> {
+        match self {
+            FormatoPedidos::Texto => {
+                let mut lector = LectorPedidos::new();
+                Ok(lineas(archivo, move |line| lector.leer(line)))
+            }
+            FormatoPedidos::JsonLines => Ok(lineas(archivo, |line| {
+                if line.trim().is_empty() {
+                    return Ok(None);
+                }
+                serde_json::from_str::<Pedido>(line)
+                    .map_err(estructura)
+                    .and_then(Pedido::completar)
+                    .map(|pedido| Some(Linea::Pedido(pedido)))
+            })),
+            FormatoPedidos::Json => {
+                let valores: Vec<serde_json::Value> = serde_json::from_reader(archivo)
+                    .map_err(|_| CafeteriaError::FormatoInvalido)?;
+                Ok(pedidos(valores, serde_json::from_value))
+            }
+            FormatoPedidos::Yaml => {
+                let valores: Vec<serde_yaml::Value> = serde_yaml::from_reader(archivo)
+                    .map_err(|_| CafeteriaError::FormatoInvalido)?;
+                Ok(pedidos(valores, serde_yaml::from_value))
+            }
+        }
+    }
+}
+
+impl FromStr for FormatoPedidos {
+    type Err = CafeteriaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(FormatoPedidos::Texto),
+            "json" => Ok(FormatoPedidos::Json),
+            "jsonl" => Ok(FormatoPedidos::JsonLines),
+            "yaml" => Ok(FormatoPedidos::Yaml),
+            _ => Err(CafeteriaError::ArgumentosInvalidos),
+        }
+    }
+}
+
+/// Lee las entradas de un archivo línea por línea, omitiendo aquellas para las que leer
+/// devuelve `None`.
+fn lineas<R, F>(archivo: R, mut leer: F) -> Entradas
+where
+    R: BufRead + Send + 'static,
+    F: FnMut(&str) -> Result<Option<Linea>, CafeteriaError> + Send + 'static,
+{
+    Box::new(
+        archivo
+            .lines()
+            .enumerate()
+            .filter_map(move |(i, line)| match line {
+                Ok(line) => {
+                    let resultado = leer(&line).map_err(|e| en_linea(e, i + 1)).transpose()?;
+                    Some(Entrada {
+                        texto: line,
+                        resultado,
+                    })
+                }
+                Err(e) => Some(Entrada {
+                    texto: String::new(),
+                    resultado: Err(CafeteriaError::LecturaArchivo(e.into())),
+                }),
+            }),
+    )
+}
+
+/// Convierte cada valor deserializado en un pedido, de forma que un valor inválido no impida
+/// leer el resto.
+fn pedidos<V, E>(valores: Vec<V>, convertir: fn(V) -> Result<Pedido, E>) -> Entradas
+where
+    V: Serialize + Send + 'static,
+    E: Display + 'static,
+{
+    Box::new(valores.into_iter().enumerate().map(move |(i, valor)| {
+        let texto = serde_json::to_string(&valor).unwrap_or_default();
+        let resultado = convertir(valor)
+            .map_err(estructura)
+            .and_then(Pedido::completar)
+            .map(Linea::Pedido)
+            .map_err(|e| en_linea(e, i + 1));
+        Entrada { texto, resultado }
+    }))
+}
+
+/// Error de la entrada con el número dado. Los errores que no son de un pedido inválido se
+/// convierten en uno con su descripción, para no perder el número de la entrada.
+fn en_linea(error: CafeteriaError, linea: usize) -> CafeteriaError {
+    match error {
+        CafeteriaError::PedidoInvalido(_) => error.en_linea(linea),
+        error => estructura(error.descripcion()).en_linea(linea),
+    }
+}
+
+/// Error de un pedido que no tiene la estructura esperada.
+fn estructura<E: Display>(error: E) -> CafeteriaError {
+    DetallePedido::new(None, Motivo::Estructura(error.to_string())).into()
+}
+
+/// Prefijo de las líneas de comentario de un archivo de pedidos.
+pub const COMENTARIO: char = '#';
+
+/// Columna del formato de pedidos con encabezado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Columna {
+    Id,
+    Agua,
+    Cafe,
+    Espuma,
+    Plazo,
+    Llegada,
+    Prioridad,
+    Cliente,
+    Bebida,
+}
+
+impl Columna {
+    /// Nombre de la columna en el encabezado.
+    #[must_use]
+    pub fn nombre(self) -> &'static str {
+        match self {
+            Columna::Id => "id",
+            Columna::Agua => "agua",
+            Columna::Cafe => "cafe",
+            Columna::Espuma => "espuma",
+            Columna::Plazo => "plazo",
+            Columna::Llegada => "llegada",
+            Columna::Prioridad => "prioridad",
+            Columna::Cliente => "cliente",
+            Columna::Bebida => "bebida",
+        }
+    }
+}
+
+impl FromStr for Columna {
+    type Err = CafeteriaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "id" => Ok(Columna::Id),
+            "agua" => Ok(Columna::Agua),
+            "cafe" => Ok(Columna::Cafe),
+            "espuma" => Ok(Columna::Espuma),
+            "plazo" => Ok(Columna::Plazo),
+            "llegada" => Ok(Columna::Llegada),
+            "prioridad" => Ok(Columna::Prioridad),
+            "cliente" => Ok(Columna::Cliente),
+            "bebida" => Ok(Columna::Bebida),
+            _ => Err(CafeteriaError::FormatoInvalido),
+        }
+    }
+}
+
+/// Versión del formato del archivo, que se determina con su primera línea con contenido.
+#[derive(Debug)]
+enum Version {
+    /// Todavía no se leyó ninguna línea con contenido
+    Desconocida,
+    /// Campos posicionales, ver [`Pedido`]
+    V1,
+    /// Encabezado con las columnas presentes, en cualquier orden
+    V2(Vec<Columna>),
+}
+
+/// Lector de las líneas de un archivo de pedidos, en cualquiera de sus dos versiones:
+///
+/// * Sin encabezado, con los campos posicionales de [`Pedido`].
+/// * Con un encabezado en la primera línea que indica el nombre de cada columna, en cualquier
+///   orden: `id`, `agua`, `cafe`, `espuma`, `plazo`, `llegada`, `prioridad`, `cliente` y
+///   `bebida`. Solo `id` es obligatoria, y `agua`, `cafe` y `espuma` pueden omitirse si se
+///   indica la `bebida`. Los valores vacíos de las columnas opcionales se ignoran, y cada línea
+///   debe tener exactamente un valor por columna.
+///
+/// En ambas versiones se ignoran las líneas vacías y las que comienzan con [`COMENTARIO`], se
+/// quitan los espacios alrededor de cada campo y se aceptan cancelaciones `cancel,<id>`.
+#[derive(Debug)]
+pub struct LectorPedidos {
+    version: Version,
+}
+
+impl LectorPedidos {
+    #[must_use]
+    pub fn new() -> Self {
+        LectorPedidos {
+            version: Version::Desconocida,
+        }
+    }
+
+    /// Lee la siguiente línea del archivo. Devuelve `None` si la línea no contiene un pedido ni
+    /// una cancelación: si está vacía, es un comentario o es el encabezado.
+    ///
+    /// La primera línea con contenido se interpreta como encabezado solo si es uno válido. En
+    /// otro caso el archivo se lee sin encabezado, y esa línea se rechaza.
+    ///
+    /// # Errors
+    /// * En caso de que la primera línea tenga alguna columna conocida pero no sea un encabezado
+    ///   válido, devuelve [`CafeteriaError::PedidoInvalido`] con el problema del encabezado.
+    /// * En caso de que la línea no sea un pedido válido devuelve [`CafeteriaError::PedidoInvalido`]
+    ///   con el campo y el motivo.
+    pub fn leer(&mut self, line: &str) -> Result<Option<Linea>, CafeteriaError> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(COMENTARIO) {
+            return Ok(None);
+        }
+        if let Version::Desconocida = self.version {
+            self.version = Version::V1;
+            let primero = line.split(',').next().unwrap_or("").trim();
+            if primero.parse::<usize>().is_err() && primero != CANCELACION {
+                match Self::encabezado(line) {
+                    Ok(columnas) => {
+                        self.version = Version::V2(columnas);
+                        return Ok(None);
+                    }
+                    // Con alguna columna conocida se trata de un encabezado inválido, y si no,
+                    // de un pedido inválido del formato sin encabezado
+                    Err(e) if line.split(',').any(|c| c.parse::<Columna>().is_ok()) => {
+                        return Err(e)
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+        match &self.version {
+            Version::V2(columnas) => match line.split_once(',') {
+                Some((campo, _)) if campo.trim() == CANCELACION => Linea::from_line(line).map(Some),
+                _ => Self::pedido(columnas, line).map(|pedido| Some(Linea::Pedido(pedido))),
+            },
+            Version::V1 | Version::Desconocida => Linea::from_line(line).map(Some),
+        }
+    }
+
+    /// Parsea las columnas del encabezado.
+    ///
+    /// # Errors
+    /// * En caso de que tenga columnas desconocidas o repetidas, o le falten columnas
+    ///   obligatorias, devuelve [`CafeteriaError::PedidoInvalido`] describiendo el problema.
+    fn encabezado(line: &str) -> Result<Vec<Columna>, CafeteriaError> {
+        let invalido = |problema: String| estructura(format!("encabezado invalido: {}", problema));
+        let mut columnas = Vec::new();
+        for nombre in line.split(',').map(str::trim) {
+            let columna: Columna = nombre
+                .parse()
+                .map_err(|_| invalido(format!("columna desconocida {}", nombre)))?;
+            if columnas.contains(&columna) {
+                return Err(invalido(format!("columna repetida {}", columna.nombre())));
+            }
+            columnas.push(columna);
+        }
+        let tiene = |columna: Columna| columnas.contains(&columna);
+        if !tiene(Columna::Id) {
+            return Err(invalido("falta la columna id".to_string()));
+        }
+        let cantidades = tiene(Columna::Agua) && tiene(Columna::Cafe) && tiene(Columna::Espuma);
+        if !(cantidades || tiene(Columna::Bebida)) {
+            return Err(invalido(
+                "faltan las columnas agua, cafe y espuma, o la columna bebida".to_string(),
+            ));
+        }
+        Ok(columnas)
+    }
+
+    /// Parsea un pedido con las columnas dadas. Las cantidades indicadas explícitamente tienen
+    /// precedencia sobre las de la bebida.
+    fn pedido(columnas: &[Columna], line: &str) -> Result<Pedido, CafeteriaError> {
+        let campos: Vec<&str> = line.split(',').map(str::trim).collect();
+        if campos.len() > columnas.len() {
+            return Err(DetallePedido::new(None, Motivo::CamposDeMas).into());
+        }
+        if let Some(columna) = columnas.get(campos.len()) {
+            return Err(DetallePedido::new(Some(columna.nombre()), Motivo::CampoFaltante).into());
+        }
+        let valor = |columna: Columna| {
+            columnas
+                .iter()
+                .position(|&c| c == columna)
+                .map(|i| campos[i])
+                .filter(|campo| !campo.is_empty())
+        };
+        let numero = |columna: Columna| {
+            valor(columna)
+                .map(|v| campo::<u64>(columna.nombre(), Some(v)))
+                .transpose()
+        };
+        let bebida = valor(Columna::Bebida)
+            .map(|nombre| {
+                Bebida::buscar(nombre).map_err(|_| {
+                    DetallePedido::new(Some(Columna::Bebida.nombre()), Motivo::BebidaDesconocida)
+                })
+            })
+            .transpose()?;
+        let cantidad =
+            |columna: Columna, receta: fn(&Bebida) -> u32| match (valor(columna), &bebida) {
+                (None, Some(bebida)) => Ok(receta(bebida)),
+                (campo_valor, _) => campo::<u32>(columna.nombre(), campo_valor),
+            };
+
+        let id = campo(Columna::Id.nombre(), valor(Columna::Id))?;
+        let mut pedido = Pedido::new(
+            id,
+            cantidad(Columna::Agua, |b| b.agua)?,
+            cantidad(Columna::Cafe, |b| b.cafe)?,
+            cantidad(Columna::Espuma, |b| b.espuma)?,
+        )?;
+        pedido.plazo = numero(Columna::Plazo)?;
+        pedido.llegada = numero(Columna::Llegada)?;
+        pedido.prioridad = valor(Columna::Prioridad)
+            .map(|v| campo::<u32>(Columna::Prioridad.nombre(), Some(v)))
+            .transpose()?
+            .unwrap_or_default();
+        pedido.cliente = valor(Columna::Cliente).map(String::from);
+        pedido.bebida = bebida.map(|bebida| bebida.nombre.to_string());
+        Ok(pedido)
+    }
+}
+
+impl Default for LectorPedidos {
+    fn default() -> Self {
+        Self::new()
+    }
+}
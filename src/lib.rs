@@ -1,6 +1,22 @@
+pub mod bebidas;
 pub mod cafe;
 pub mod cafetera;
+pub mod cli;
+pub mod cola;
+pub mod comparacion;
+pub mod configuracion;
 pub mod constantes;
 pub mod error;
 pub mod espuma;
+pub mod estadisticas;
+pub mod generador;
+pub mod lectura;
+pub mod log;
+pub mod metricas;
 pub mod pedido;
+pub mod recuperacion;
+pub mod reporte;
+pub mod tiempos;
+pub mod traza;
+pub mod utilizacion;
+pub mod validacion;
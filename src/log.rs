@@ -0,0 +1,190 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Instant;
+
+use crate::error::CafeteriaError;
+
+/// Variable de entorno con el nivel de log a utilizar.
+pub const VARIABLE_NIVEL: &str = "CAFETERIA_LOG";
+
+/// Nivel de importancia de un mensaje.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Nivel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Nivel {
+    fn desde_u8(valor: u8) -> Nivel {
+        match valor {
+            0 => Nivel::Error,
+            1 => Nivel::Warn,
+            2 => Nivel::Info,
+            _ => Nivel::Debug,
+        }
+    }
+}
+
+impl FromStr for Nivel {
+    type Err = CafeteriaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "error" => Ok(Nivel::Error),
+            "warn" => Ok(Nivel::Warn),
+            "info" => Ok(Nivel::Info),
+            "debug" => Ok(Nivel::Debug),
+            _ => Err(CafeteriaError::ArgumentosInvalidos),
+        }
+    }
+}
+
+impl fmt::Display for Nivel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag = match self {
+            Nivel::Error => "ERROR",
+            Nivel::Warn => "WARN",
+            Nivel::Info => "INFO",
+            Nivel::Debug => "DEBUG",
+        };
+        write!(f, "{}", tag)
+    }
+}
+
+/// Opciones de formato y destino de los mensajes.
+struct Salida {
+    timestamps: bool,
+    hilos: bool,
+    archivo: Option<File>,
+}
+
+static NIVEL: AtomicU8 = AtomicU8::new(Nivel::Info as u8);
+static SALIDA: Mutex<Salida> = Mutex::new(Salida {
+    timestamps: false,
+    hilos: false,
+    archivo: None,
+});
+static INICIO: OnceLock<Instant> = OnceLock::new();
+
+/// Nivel máximo de los mensajes que se registran.
+pub fn nivel() -> Nivel {
+    Nivel::desde_u8(NIVEL.load(Ordering::Relaxed))
+}
+
+/// Cambia el nivel máximo de los mensajes que se registran.
+pub fn set_nivel(nivel: Nivel) {
+    NIVEL.store(nivel as u8, Ordering::Relaxed);
+}
+
+/// Toma el nivel de la variable de entorno [`VARIABLE_NIVEL`], si está definida.
+///
+/// # Errors
+/// * En caso de que el valor de la variable no sea un nivel válido, devuelve [`CafeteriaError::ArgumentosInvalidos`].
+pub fn nivel_desde_entorno() -> Result<(), CafeteriaError> {
+    if let Ok(valor) = std::env::var(VARIABLE_NIVEL) {
+        set_nivel(valor.parse()?);
+    }
+    Ok(())
+}
+
+/// Agrega a cada mensaje el tiempo transcurrido desde el inicio del programa y el nombre del
+/// hilo que lo emitió.
+pub fn set_formato(timestamps: bool, hilos: bool) {
+    INICIO.get_or_init(Instant::now);
+    if let Ok(mut salida) = SALIDA.lock() {
+        salida.timestamps = timestamps;
+        salida.hilos = hilos;
+    }
+}
+
+/// Escribe los mensajes en el archivo dado, o deja de hacerlo si es `None`. Mientras haya un
+/// archivo configurado, por consola solo se muestran los mensajes de nivel [`Nivel::Info`] o
+/// más importantes.
+///
+/// # Errors
+/// * En caso de error al crear el archivo, devuelve [`CafeteriaError::CreacionArchivo`].
+pub fn set_archivo(ruta: Option<&str>) -> Result<(), CafeteriaError> {
+    let archivo = ruta
+        .map(File::create)
+        .transpose()
+        .map_err(|e| CafeteriaError::CreacionArchivo(e.into()))?;
+    SALIDA.lock()?.archivo = archivo;
+    Ok(())
+}
+
+/// Registra un mensaje con el nivel dado. Se utiliza mediante las macros [`error!`](crate::error!),
+/// [`warn!`](crate::warn!), [`info!`](crate::info!) y [`debug!`](crate::debug!).
+pub fn registrar(nivel: Nivel, mensaje: fmt::Arguments) {
+    if nivel > self::nivel() {
+        return;
+    }
+    let Ok(mut salida) = SALIDA.lock() else {
+        println!("[{}] {}", nivel, mensaje);
+        return;
+    };
+
+    let mut linea = String::new();
+    if salida.timestamps {
+        let transcurrido = INICIO.get_or_init(Instant::now).elapsed();
+        linea.push_str(&format!("{:>9.3} ", transcurrido.as_secs_f64()));
+    }
+    linea.push_str(&format!("[{}] ", nivel));
+    if salida.hilos {
+        let hilo = thread::current();
+        match hilo.name() {
+            Some(nombre) => linea.push_str(&format!("({}) ", nombre)),
+            None => linea.push_str(&format!("({:?}) ", hilo.id())),
+        }
+    }
+    linea.push_str(&mensaje.to_string());
+
+    match salida.archivo.as_mut() {
+        Some(archivo) => {
+            // Si no se puede escribir el archivo no hay otro lugar donde reportarlo
+            let _ = writeln!(archivo, "{}", linea);
+            if nivel <= Nivel::Info {
+                println!("{}", linea);
+            }
+        }
+        None => println!("{}", linea),
+    }
+}
+
+/// Registra un mensaje de nivel [`Nivel::Error`].
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log::registrar($crate::log::Nivel::Error, format_args!($($arg)*))
+    };
+}
+
+/// Registra un mensaje de nivel [`Nivel::Warn`].
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::registrar($crate::log::Nivel::Warn, format_args!($($arg)*))
+    };
+}
+
+/// Registra un mensaje de nivel [`Nivel::Info`].
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::registrar($crate::log::Nivel::Info, format_args!($($arg)*))
+    };
+}
+
+/// Registra un mensaje de nivel [`Nivel::Debug`].
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::registrar($crate::log::Nivel::Debug, format_args!($($arg)*))
+    };
+}
@@ -1,25 +1,226 @@
 use cafeteria::cafetera::Cafetera;
-use cafeteria::error::CafeteriaError;
-use cafeteria::pedido::generate_file;
-
-const FILE_COMMAND: &str = "-f";
-
-fn main() -> Result<(), CafeteriaError> {
-    let args: Vec<String> = std::env::args().collect();
-    match args.get(1) {
-        Some(arg) => {
-            if arg == FILE_COMMAND {
-                let filename = args.get(2).ok_or(CafeteriaError::ArgumentosInvalidos)?;
-                let n = args.get(3);
-                generate_file(filename, n)
-            } else {
-                let cafetera = Cafetera::new();
-                cafetera.realizar_pedidos(arg)
-            }
+use cafeteria::cli::{self, Comando, OpcionesCorrida};
+use cafeteria::comparacion::Comparacion;
+use cafeteria::error::{
+    CafeteriaError, SALIDA_ARGUMENTOS, SALIDA_CORRIDA_PARCIAL, SALIDA_PEDIDOS_INVALIDOS,
+};
+use cafeteria::lectura::{escribir_rechazados, FormatoPedidos};
+use cafeteria::log;
+use cafeteria::metricas::ServidorMetricas;
+use cafeteria::reporte::{FormatoReporte, Reporte};
+use cafeteria::validacion::Validacion;
+use cafeteria::{info, warn};
+use std::io::BufRead;
+use std::process::ExitCode;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(unix)]
+use std::sync::Arc;
+
+/// Código de salida por defecto si la corrida fue interrumpida
+const SALIDA_INTERRUMPIDA: u8 = 130;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let comando = match cli::parsear(&args) {
+        Ok(comando) => comando,
+        Err(e) => {
+            eprintln!("{}\n{}", e.mensaje, e.sugerencia());
+            return ExitCode::from(SALIDA_ARGUMENTOS);
+        }
+    };
+    match ejecutar(comando) {
+        Ok(codigo) => codigo,
+        Err(error) => {
+            eprintln!("Error: {}", error.descripcion());
+            ExitCode::from(error.codigo_salida())
+        }
+    }
+}
+
+/// Ejecuta el comando dado, devolviendo el código con el que debe finalizar el programa.
+fn ejecutar(comando: Comando) -> Result<ExitCode, CafeteriaError> {
+    match comando {
+        Comando::Ayuda(ayuda) => {
+            print!("{}", ayuda);
+            Ok(ExitCode::SUCCESS)
+        }
+        Comando::Correr { ruta, opciones } => {
+            let formato = opciones
+                .formato
+                .unwrap_or_else(|| FormatoPedidos::desde_ruta(&ruta));
+            correr(&opciones, |cafetera| {
+                cafetera.realizar_pedidos_con_formato(&ruta, formato)
+            })
+        }
+        Comando::Generar { ruta, generador } => {
+            generador.escribir(&ruta)?;
+            Ok(ExitCode::SUCCESS)
         }
+        Comando::Validar { ruta, formato } => validar(&ruta, formato),
+        Comando::Simular {
+            generador,
+            opciones,
+        } => correr(&opciones, |cafetera| {
+            cafetera.realizar_entradas(generador.entradas())
+        }),
+        Comando::Reportar { ruta, salida } => {
+            reportar(&ruta, salida.as_deref())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Comando::Comparar {
+            anterior,
+            actual,
+            umbral,
+        } => {
+            comparar(&anterior, &actual, umbral)?;
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// Crea una cafetera con las opciones dadas y realiza sus pedidos mediante la función dada,
+/// exportando la traza, las métricas, el reporte y los pedidos rechazados según las opciones.
+/// Devuelve el código de salida según cómo finalizó la corrida: interrumpida por una señal, con
+/// pedidos rechazados en modo estricto o sin servir todos los pedidos.
+fn correr<F>(opciones: &OpcionesCorrida, realizar: F) -> Result<ExitCode, CafeteriaError>
+where
+    F: FnOnce(&Cafetera) -> Result<(), CafeteriaError>,
+{
+    log::nivel_desde_entorno()?;
+    if let Some(nivel) = opciones.nivel {
+        log::set_nivel(nivel);
+    }
+    log::set_formato(opciones.timestamps, opciones.hilos);
+    log::set_archivo(opciones.log.as_deref())?;
+
+    let cafetera = Cafetera::con_configuracion(opciones.configuracion.clone());
+    #[cfg(unix)]
+    signal_hook::flag::register(
+        signal_hook::consts::SIGUSR1,
+        cafetera.indicador_estadisticas(),
+    )
+    .map_err(|e| CafeteriaError::Senal(e.into()))?;
+    #[cfg(unix)]
+    let senal = registrar_apagado(&cafetera)?;
+    consola(cafetera.clone())?;
+    let servidor = opciones
+        .metricas
+        .as_deref()
+        .map(|direccion| ServidorMetricas::iniciar(cafetera.clone(), direccion))
+        .transpose()?;
+    realizar(&cafetera)?;
+    if let Some(servidor) = servidor {
+        servidor.esperar();
+    }
+    if let Some(ruta) = &opciones.reporte {
+        Reporte::generar(&cafetera)?.escribir(ruta)?;
+    }
+    if let Some(ruta) = &opciones.traza {
+        cafetera.traza.escribir(ruta)?;
+    }
+    if let Some(ruta) = &opciones.rechazados {
+        escribir_rechazados(ruta, &cafetera.rechazados()?)?;
+    }
+    if cafetera.detenida() {
+        #[cfg(unix)]
+        let codigo = match senal.load(Ordering::Relaxed) {
+            0 => SALIDA_INTERRUMPIDA,
+            senal => 128 + senal as u8,
+        };
+        #[cfg(not(unix))]
+        let codigo = SALIDA_INTERRUMPIDA;
+        info!("Corrida interrumpida");
+        return Ok(ExitCode::from(codigo));
+    }
+    let estadisticas = cafetera.snapshot();
+    if opciones.estricto && estadisticas.pedidos_rechazados > 0 {
+        warn!(
+            "Se rechazaron {} pedidos en modo estricto",
+            estadisticas.pedidos_rechazados
+        );
+        return Ok(ExitCode::from(SALIDA_PEDIDOS_INVALIDOS));
+    }
+    if estadisticas.es_parcial() {
+        warn!("Corrida parcial, no se sirvieron todos los pedidos");
+        return Ok(ExitCode::from(SALIDA_CORRIDA_PARCIAL));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Ejecuta en un hilo aparte los comandos que se escriban por consola (`pause`, `resume`,
+/// `stats` o `stop`), uno por línea.
+fn consola(cafetera: Cafetera) -> Result<(), CafeteriaError> {
+    // El hilo queda bloqueado leyendo la entrada, por lo que no se espera su finalización
+    std::thread::Builder::new()
+        .name("consola".to_string())
+        .spawn(move || {
+            for linea in std::io::stdin().lock().lines() {
+                let Ok(linea) = linea else {
+                    break;
+                };
+                if linea.trim().is_empty() {
+                    continue;
+                }
+                if let Err(e) = cafetera.ejecutar(&linea) {
+                    warn!("Comando invalido {:?}: {}", linea.trim(), e.descripcion());
+                }
+            }
+        })
+        .map_err(|e| CafeteriaError::CreacionHilo(e.into()))?;
+    Ok(())
+}
+
+/// Detiene la cafetera al recibir SIGINT o SIGTERM. Una segunda señal finaliza el proceso
+/// inmediatamente. Devuelve la última señal recibida, o 0 si no se recibió ninguna.
+#[cfg(unix)]
+fn registrar_apagado(cafetera: &Cafetera) -> Result<Arc<AtomicUsize>, CafeteriaError> {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+    use signal_hook::flag;
+
+    let senal = Arc::new(AtomicUsize::new(0));
+    let apagado = cafetera.indicador_apagado();
+    for s in [SIGINT, SIGTERM] {
+        flag::register_conditional_shutdown(s, 128 + s, apagado.clone())
+            .and_then(|_| flag::register(s, apagado.clone()))
+            .and_then(|_| flag::register_usize(s, senal.clone(), s as usize))
+            .map_err(|e| CafeteriaError::Senal(e.into()))?;
+    }
+    Ok(senal)
+}
+
+/// Valida el archivo de pedidos dado sin correr la cafetera e imprime un resumen. Si el
+/// archivo tiene errores, devuelve [`SALIDA_PEDIDOS_INVALIDOS`].
+fn validar(ruta: &str, formato: Option<FormatoPedidos>) -> Result<ExitCode, CafeteriaError> {
+    let formato = formato.unwrap_or_else(|| FormatoPedidos::desde_ruta(ruta));
+    let validacion = Validacion::validar(ruta, formato)?;
+    for linea in validacion.lineas() {
+        println!("{}", linea);
+    }
+    if !validacion.es_valida() {
+        return Ok(ExitCode::from(SALIDA_PEDIDOS_INVALIDOS));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Muestra un reporte en formato JSON como Markdown, o lo convierte al formato de la ruta de
+/// salida si se indica.
+fn reportar(ruta: &str, salida: Option<&str>) -> Result<(), CafeteriaError> {
+    let reporte = Reporte::leer(ruta)?;
+    match salida {
+        Some(salida) => reporte.escribir(salida),
         None => {
-            println!("No se especificó la ruta del archivo de pedidos");
-            Err(CafeteriaError::ArgumentosInvalidos)
+            print!("{}", reporte.formatear(FormatoReporte::Markdown)?);
+            Ok(())
         }
     }
 }
+
+/// Compara dos reportes en formato JSON e imprime las diferencias.
+fn comparar(anterior: &str, actual: &str, umbral: f64) -> Result<(), CafeteriaError> {
+    let (anterior, actual) = (Reporte::leer(anterior)?, Reporte::leer(actual)?);
+    for linea in Comparacion::new(&anterior, &actual, umbral).lineas() {
+        println!("{}", linea);
+    }
+    Ok(())
+}
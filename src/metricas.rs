@@ -0,0 +1,286 @@
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::cafetera::{lanzar, Cafetera};
+use crate::error::CafeteriaError;
+use crate::estadisticas::EstadoDispensador;
+use crate::tiempos::TiemposPedido;
+use crate::{info, warn};
+
+/// Límites superiores en segundos de los buckets de los histogramas de latencia.
+const BUCKETS: [f64; 9] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+/// Tiempo de espera en milisegundos entre cada intento de aceptar una conexión.
+const ESPERA_CONEXION: u64 = 100;
+/// Tiempo máximo en milisegundos que se espera al leer o escribir en una conexión, para que un
+/// cliente inactivo no bloquee al servidor.
+const TIEMPO_CONEXION: u64 = 1000;
+
+/// Latencia de un pedido a partir de sus tiempos.
+type Latencia = fn(&TiemposPedido) -> Duration;
+
+/// Servidor HTTP que expone las métricas de una [`Cafetera`] en formato de texto de Prometheus
+/// en la ruta `/metrics`, y permite pausarla, reanudarla o detenerla mediante `POST` a `/pause`,
+/// `/resume` o `/stop`.
+pub struct ServidorMetricas {
+    direccion: SocketAddr,
+    handle: JoinHandle<()>,
+}
+
+impl ServidorMetricas {
+    /// Inicia el servidor en la dirección dada en un hilo aparte. El servidor atiende pedidos
+    /// hasta que finaliza la corrida de la cafetera.
+    ///
+    /// # Errors
+    /// * En caso de no poder escuchar en la dirección dada, devuelve [`CafeteriaError::ServidorMetricas`].
+    /// * En caso de que no se pueda crear el hilo del servidor, devuelve [`CafeteriaError::CreacionHilo`].
+    pub fn iniciar(
+        cafetera: Cafetera,
+        direccion: &str,
+    ) -> Result<ServidorMetricas, CafeteriaError> {
+        let listener =
+            TcpListener::bind(direccion).map_err(|e| CafeteriaError::ServidorMetricas(e.into()))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| CafeteriaError::ServidorMetricas(e.into()))?;
+        let direccion = listener
+            .local_addr()
+            .map_err(|e| CafeteriaError::ServidorMetricas(e.into()))?;
+        info!("Metricas disponibles en http://{}/metrics", direccion);
+
+        let handle = lanzar("metricas".to_string(), move || loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if atender(stream, &cafetera).is_err() {
+                        warn!("Error al responder un pedido de metricas");
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if cafetera.finalizada().unwrap_or(true) {
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(ESPERA_CONEXION));
+                }
+                Err(_) => warn!("Error al aceptar una conexion de metricas"),
+            }
+        })?;
+
+        Ok(ServidorMetricas { direccion, handle })
+    }
+
+    /// Dirección en la que escucha el servidor.
+    #[must_use]
+    pub fn direccion(&self) -> SocketAddr {
+        self.direccion
+    }
+
+    /// Espera a que el servidor finalice.
+    pub fn esperar(self) {
+        if self.handle.join().is_err() {
+            warn!("Error en el join de un hilo");
+        }
+    }
+}
+
+/// Responde un pedido HTTP con las métricas si la ruta es `/metrics`, ejecuta el comando
+/// correspondiente ante un `POST` a `/pause`, `/resume` o `/stop`, o responde con 404 en otro caso.
+fn atender(mut stream: TcpStream, cafetera: &Cafetera) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_millis(TIEMPO_CONEXION)))?;
+    stream.set_write_timeout(Some(Duration::from_millis(TIEMPO_CONEXION)))?;
+    let mut lector = BufReader::new(stream.try_clone()?);
+    let mut primera = String::new();
+    lector.read_line(&mut primera)?;
+    let mut linea = String::new();
+    while lector.read_line(&mut linea)? > 0 && linea.trim_end() != "" {
+        linea.clear();
+    }
+
+    let mut partes = primera.split_whitespace();
+    let (metodo, ruta) = (partes.next().unwrap_or(""), partes.next().unwrap_or(""));
+    let (estado, cuerpo) = match (metodo, ruta) {
+        ("POST", "/pause" | "/resume" | "/stop") => match cafetera.ejecutar(&ruta[1..]) {
+            Ok(()) => ("200 OK", "ok\n".to_string()),
+            Err(e) => (
+                "500 Internal Server Error",
+                format!("{}\n", e.descripcion()),
+            ),
+        },
+        (_, "/metrics") => match exportar(cafetera) {
+            Ok(cuerpo) => ("200 OK", cuerpo),
+            Err(e) => (
+                "500 Internal Server Error",
+                format!("{}\n", e.descripcion()),
+            ),
+        },
+        _ => ("404 Not Found", String::new()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        estado,
+        cuerpo.len(),
+        cuerpo
+    )?;
+    stream.flush()
+}
+
+/// Genera las métricas de la cafetera en formato de texto de Prometheus.
+///
+/// # Errors
+/// * En caso de que algún lock se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+pub fn exportar(cafetera: &Cafetera) -> Result<String, CafeteriaError> {
+    let mut salida = String::new();
+    let estadisticas = cafetera.snapshot();
+    let contenedores = [
+        (
+            "cafe_molido",
+            estadisticas.cafe_molido,
+            estadisticas.cafe_consumido,
+        ),
+        (
+            "granos",
+            estadisticas.granos,
+            estadisticas.granos_consumidos,
+        ),
+        ("espuma", estadisticas.espuma, estadisticas.espuma_consumida),
+        ("leche", estadisticas.leche, estadisticas.leche_consumida),
+    ];
+
+    encabezado(
+        &mut salida,
+        "cafeteria_contenedor",
+        "gauge",
+        "Cantidad actual en cada contenedor.",
+    );
+    for (nombre, actual, _) in contenedores {
+        let _ = writeln!(
+            salida,
+            "cafeteria_contenedor{{contenedor=\"{}\"}} {}",
+            nombre, actual
+        );
+    }
+    encabezado(
+        &mut salida,
+        "cafeteria_consumido_total",
+        "counter",
+        "Cantidad total consumida de cada producto.",
+    );
+    for (nombre, _, consumido) in contenedores {
+        let _ = writeln!(
+            salida,
+            "cafeteria_consumido_total{{producto=\"{}\"}} {}",
+            nombre, consumido
+        );
+    }
+
+    encabezado(
+        &mut salida,
+        "cafeteria_pedidos_completados_total",
+        "counter",
+        "Cantidad de pedidos completados.",
+    );
+    let _ = writeln!(
+        salida,
+        "cafeteria_pedidos_completados_total {}",
+        estadisticas.cant_pedidos
+    );
+
+    encabezado(
+        &mut salida,
+        "cafeteria_dispensador_ocupado",
+        "gauge",
+        "1 si el dispensador se encuentra preparando un pedido.",
+    );
+    for (i, estado) in estadisticas.dispensadores.iter().enumerate() {
+        let _ = writeln!(
+            salida,
+            "cafeteria_dispensador_ocupado{{dispensador=\"{}\"}} {}",
+            i,
+            u8::from(*estado == EstadoDispensador::Ocupado)
+        );
+    }
+
+    encabezado(
+        &mut salida,
+        "cafeteria_clientes_en_cola",
+        "gauge",
+        "Cantidad de clientes esperando un dispensador.",
+    );
+    let _ = writeln!(
+        salida,
+        "cafeteria_clientes_en_cola {}",
+        estadisticas.clientes_en_cola
+    );
+    encabezado(
+        &mut salida,
+        "cafeteria_pausada",
+        "gauge",
+        "1 si la cafetera se encuentra pausada.",
+    );
+    let _ = writeln!(
+        salida,
+        "cafeteria_pausada {}",
+        u8::from(estadisticas.pausada)
+    );
+    encabezado(
+        &mut salida,
+        "cafeteria_pausa_segundos_total",
+        "counter",
+        "Tiempo total que la cafetera estuvo pausada.",
+    );
+    let _ = writeln!(
+        salida,
+        "cafeteria_pausa_segundos_total {}",
+        estadisticas.tiempo_pausado_ms as f64 / 1000.0
+    );
+
+    let tiempos = cafetera.tiempos.lock()?;
+    let latencias: [(&str, &str, Latencia); 4] = [
+        (
+            "cafeteria_espera_dispensador_segundos",
+            "Tiempo de espera por un dispensador.",
+            TiemposPedido::espera_dispensador,
+        ),
+        (
+            "cafeteria_espera_cafe_segundos",
+            "Tiempo de espera por el cafe.",
+            TiemposPedido::espera_cafe,
+        ),
+        (
+            "cafeteria_espera_espuma_segundos",
+            "Tiempo de espera por la espuma.",
+            TiemposPedido::espera_espuma,
+        ),
+        (
+            "cafeteria_pedido_segundos",
+            "Tiempo total de cada pedido.",
+            TiemposPedido::total,
+        ),
+    ];
+    for (nombre, ayuda, latencia) in latencias {
+        encabezado(&mut salida, nombre, "histogram", ayuda);
+        let muestras: Vec<f64> = tiempos.iter().map(|t| latencia(t).as_secs_f64()).collect();
+        for limite in BUCKETS {
+            let cant = muestras.iter().filter(|&&m| m <= limite).count();
+            let _ = writeln!(salida, "{}_bucket{{le=\"{}\"}} {}", nombre, limite, cant);
+        }
+        let _ = writeln!(
+            salida,
+            "{}_bucket{{le=\"+Inf\"}} {}",
+            nombre,
+            muestras.len()
+        );
+        let _ = writeln!(salida, "{}_sum {}", nombre, muestras.iter().sum::<f64>());
+        let _ = writeln!(salida, "{}_count {}", nombre, muestras.len());
+    }
+
+    Ok(salida)
+}
+
+fn encabezado(salida: &mut String, nombre: &str, tipo: &str, ayuda: &str) {
+    let _ = writeln!(salida, "# HELP {} {}", nombre, ayuda);
+    let _ = writeln!(salida, "# TYPE {} {}", nombre, tipo);
+}
@@ -1,55 +1,201 @@
 use rand::Rng;
-use std::fs::File;
-use std::io::Write;
+use serde::{Deserialize, Serialize};
+use std::num::ParseIntError;
+use std::str::FromStr;
 
+use crate::bebidas::Bebida;
 use crate::constantes::{MAX_CANTIDAD, MIN_CANTIDAD};
-use crate::error::CafeteriaError;
+use crate::error::{CafeteriaError, DetallePedido, Motivo};
+use crate::generador::Generador;
 
-const CANT_PEDIDOS: usize = 15;
+/// Cantidad de pedidos a generar si no se indica otra.
+pub const CANT_PEDIDOS: usize = 15;
+
+/// Forma en la que terminó un pedido.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resultado {
+    /// Se sirvieron el agua, el café y la espuma
+    Completado,
+    /// Se interrumpió por un apagado en modo [`ModoApagado::Abortar`](crate::configuracion::ModoApagado::Abortar)
+    Abortado,
+    /// Se canceló mediante [`Cafetera::cancelar`](crate::cafetera::Cafetera::cancelar)
+    Cancelado,
+    /// Se abandonó por superar su plazo esperando un dispensador, café o espuma
+    Timeout,
+    /// El cliente se fue sin pedir porque la cola estaba llena
+    Desistido,
+    /// El cliente se fue de la cola por superar su paciencia
+    Abandonado,
+    /// El hilo del pedido entró en pánico y se agotaron sus reintentos
+    Fallido,
+}
+
+/// Efecto de cancelar un pedido mediante
+/// [`Cafetera::cancelar`](crate::cafetera::Cafetera::cancelar).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cancelacion {
+    /// El pedido ya se recibió y todavía no finalizó, por lo que se descarta o se interrumpe
+    EnCurso,
+    /// El pedido todavía no se recibió, por lo que se descartará al recibirlo
+    Anticipada,
+    /// El pedido ya finalizó, por lo que no se cancela
+    Finalizado,
+}
+
+/// Primer campo de las líneas del archivo de pedidos que cancelan un pedido.
+pub const CANCELACION: &str = "cancel";
+
+/// Línea de un archivo de pedidos.
+#[derive(Debug)]
+pub enum Linea {
+    /// Pedido a preparar
+    Pedido(Pedido),
+    /// Cancelación del pedido con el id dado, representada como `cancel,<id>`
+    Cancelacion(usize),
+}
+
+impl Linea {
+    /// Parsea una línea de un archivo de pedidos, que puede ser un pedido o una cancelación.
+    ///
+    /// # Errors
+    /// * En caso de que la línea no sea una cancelación ni un pedido válido devuelve
+    ///   [`CafeteriaError::PedidoInvalido`] con el campo y el motivo.
+    pub fn from_line(line: &str) -> Result<Linea, CafeteriaError> {
+        match line.split_once(',') {
+            Some((primero, id)) if primero.trim() == CANCELACION => {
+                Ok(Linea::Cancelacion(campo("id", Some(id))?))
+            }
+            _ => Ok(Linea::Pedido(Pedido::from_line(line)?)),
+        }
+    }
+}
 
 /// Información del pedido de un cliente.
 ///
 /// Se representa como una línea en el archivo de pedidos de la siguiente manera:
 ///
-/// `<id>,<agua>,<cafe>,<espuma>`
-#[derive(Debug)]
+/// `<id>,<agua>,<cafe>,<espuma>[,<plazo>[,<llegada>]]`
+///
+/// El plazo puede dejarse vacío para indicar solo la llegada. El resto de los campos solo
+/// pueden indicarse en el formato con encabezado de [`LectorPedidos`](crate::lectura::LectorPedidos).
+///
+/// En los formatos JSON y YAML se representa como un objeto con un campo por cada atributo,
+/// donde solo `id` es obligatorio y las cantidades pueden omitirse si se indica la bebida.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Pedido {
     pub id: usize,
     /// Cantidad de agua
+    #[serde(default)]
     pub agua: u32,
     /// Cantidad de cafe
+    #[serde(default)]
     pub cafe: u32,
     /// Cantidad de espuma
+    #[serde(default)]
     pub espuma: u32,
+    /// Tiempo máximo en milisegundos que el cliente espera desde su llegada a que se le asigne
+    /// un dispensador y se le sirva café y espuma
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plazo: Option<u64>,
+    /// Momento de llegada del cliente en milisegundos desde el inicio de la corrida
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llegada: Option<u64>,
+    /// Los clientes con mayor prioridad reciben un dispensador antes que el resto
+    #[serde(default, skip_serializing_if = "es_cero")]
+    pub prioridad: u32,
+    /// Nombre del cliente
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cliente: Option<String>,
+    /// Nombre de la bebida pedida
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bebida: Option<String>,
+}
+
+fn es_cero(valor: &u32) -> bool {
+    *valor == 0
+}
+
+/// Parsea el valor numérico del campo con el nombre dado.
+///
+/// # Errors
+/// * En caso de que el valor no esté o esté vacío, no sea un número o sea demasiado grande,
+///   devuelve [`CafeteriaError::PedidoInvalido`] con el nombre del campo.
+pub(crate) fn campo<T>(nombre: &'static str, valor: Option<&str>) -> Result<T, CafeteriaError>
+where
+    T: FromStr<Err = ParseIntError>,
+{
+    let valor = valor.map(str::trim).unwrap_or_default();
+    valor.parse().map_err(|e| match CafeteriaError::from(e) {
+        CafeteriaError::PedidoInvalido(detalle) => DetallePedido {
+            campo: Some(nombre),
+            ..detalle
+        }
+        .into(),
+        error => error,
+    })
 }
 
 impl Pedido {
     /// # Errors
     /// * En caso de que agua, cafe o espuma no estén en el rango [[`MIN_CANTIDAD`], =[`MAX_CANTIDAD`]]
-    /// devuelve [`CafeteriaError::PedidoInvalido`].
+    ///   devuelve [`CafeteriaError::PedidoInvalido`].
     pub fn new(id: usize, agua: u32, cafe: u32, espuma: u32) -> Result<Pedido, CafeteriaError> {
-        if !(MIN_CANTIDAD..=MAX_CANTIDAD).contains(&agua)
-            || !(MIN_CANTIDAD..=MAX_CANTIDAD).contains(&cafe)
-            || !(MIN_CANTIDAD..=MAX_CANTIDAD).contains(&espuma)
-        {
-            Err(CafeteriaError::PedidoInvalido)
-        } else {
-            Ok(Pedido {
-                id,
-                agua,
-                cafe,
-                espuma,
-            })
+        for (nombre, cantidad) in [("agua", agua), ("cafe", cafe), ("espuma", espuma)] {
+            if !(MIN_CANTIDAD..=MAX_CANTIDAD).contains(&cantidad) {
+                return Err(DetallePedido::new(Some(nombre), Motivo::FueraDeRango).into());
+            }
+        }
+        Ok(Pedido {
+            id,
+            agua,
+            cafe,
+            espuma,
+            ..Default::default()
+        })
+    }
+
+    /// Completa las cantidades no indicadas (en 0) con las de la bebida, si la tiene, y
+    /// verifica que el pedido sea válido. Se utiliza con los pedidos deserializados.
+    ///
+    /// # Errors
+    /// * En caso de que la bebida no exista devuelve [`CafeteriaError::PedidoInvalido`].
+    /// * En caso de que agua, cafe o espuma no estén en el rango [[`MIN_CANTIDAD`], =[`MAX_CANTIDAD`]]
+    ///   devuelve [`CafeteriaError::PedidoInvalido`].
+    pub fn completar(mut self) -> Result<Pedido, CafeteriaError> {
+        if let Some(nombre) = &self.bebida {
+            let bebida = Bebida::buscar(nombre)
+                .map_err(|_| DetallePedido::new(Some("bebida"), Motivo::BebidaDesconocida))?;
+            for (cantidad, receta) in [
+                (&mut self.agua, bebida.agua),
+                (&mut self.cafe, bebida.cafe),
+                (&mut self.espuma, bebida.espuma),
+            ] {
+                if *cantidad == 0 {
+                    *cantidad = receta;
+                }
+            }
+            self.bebida = Some(bebida.nombre.to_string());
         }
+        Pedido::new(self.id, self.agua, self.cafe, self.espuma)?;
+        Ok(self)
     }
 
     /// Genera un pedido con cantidades aleatorias de agua, café y espuma.
     pub fn new_random(id: usize) -> Pedido {
+        Self::aleatorio(id, &mut rand::thread_rng())
+    }
+
+    /// Genera un pedido con cantidades aleatorias de agua, café y espuma utilizando el
+    /// generador de números aleatorios dado.
+    pub fn aleatorio<R: Rng>(id: usize, rng: &mut R) -> Pedido {
         Pedido {
             id,
-            agua: rand::thread_rng().gen_range(MIN_CANTIDAD..=MAX_CANTIDAD),
-            cafe: rand::thread_rng().gen_range(MIN_CANTIDAD..=MAX_CANTIDAD),
-            espuma: rand::thread_rng().gen_range(MIN_CANTIDAD..=MAX_CANTIDAD),
+            agua: rng.gen_range(MIN_CANTIDAD..=MAX_CANTIDAD),
+            cafe: rng.gen_range(MIN_CANTIDAD..=MAX_CANTIDAD),
+            espuma: rng.gen_range(MIN_CANTIDAD..=MAX_CANTIDAD),
+            ..Default::default()
         }
     }
 
@@ -57,45 +203,65 @@ impl Pedido {
     ///
     /// # Errors
     /// * En caso de que agua, cafe o espuma no estén en el rango [[`MIN_CANTIDAD`], =[`MAX_CANTIDAD`]]
-    /// devuelve [`CafeteriaError::PedidoInvalido`].
-    /// * En caso de que la línea no tenga el formato correcto devuelve [`CafeteriaError::PedidoInvalido`].
+    ///   devuelve [`CafeteriaError::PedidoInvalido`] con [`Motivo::FueraDeRango`].
+    /// * En caso de que falte alguno de los campos obligatorios devuelve [`CafeteriaError::PedidoInvalido`]
+    ///   con [`Motivo::CampoFaltante`].
+    /// * En caso de que algún campo, o el plazo o la llegada si están presentes, no sean un número
+    ///   devuelve [`CafeteriaError::PedidoInvalido`] con [`Motivo::NoNumerico`].
+    /// * En caso de que la línea tenga campos de más devuelve [`CafeteriaError::PedidoInvalido`] con
+    ///   [`Motivo::CamposDeMas`].
     pub fn from_line(line: &str) -> Result<Pedido, CafeteriaError> {
-        let mut pedido = line.split(',');
-        let id = pedido
-            .next()
-            .ok_or(CafeteriaError::PedidoInvalido)?
-            .parse::<usize>()?;
-        let agua = pedido
-            .next()
-            .ok_or(CafeteriaError::PedidoInvalido)?
-            .parse::<u32>()?;
-        let cafe = pedido
-            .next()
-            .ok_or(CafeteriaError::PedidoInvalido)?
-            .parse::<u32>()?;
-        let espuma = pedido
-            .next()
-            .ok_or(CafeteriaError::PedidoInvalido)?
-            .parse::<u32>()?;
-        Pedido::new(id, agua, cafe, espuma)
+        let mut pedido = line.split(',').map(str::trim);
+        let id = campo("id", pedido.next())?;
+        let agua = campo("agua", pedido.next())?;
+        let cafe = campo("cafe", pedido.next())?;
+        let espuma = campo("espuma", pedido.next())?;
+        let mut opcional = |nombre| {
+            pedido
+                .next()
+                .filter(|valor| !valor.is_empty())
+                .map(|valor| campo::<u64>(nombre, Some(valor)))
+                .transpose()
+        };
+        let plazo = opcional("plazo")?;
+        let llegada = opcional("llegada")?;
+        if pedido.next().is_some() {
+            return Err(DetallePedido::new(None, Motivo::CamposDeMas).into());
+        }
+        let mut pedido = Pedido::new(id, agua, cafe, espuma)?;
+        pedido.plazo = plazo;
+        pedido.llegada = llegada;
+        Ok(pedido)
     }
 
     /// Parseo a String.
     pub fn to_line(&self) -> String {
-        format!("{},{},{},{}", self.id, self.agua, self.cafe, self.espuma)
+        let mut linea = format!("{},{},{},{}", self.id, self.agua, self.cafe, self.espuma);
+        let opcional = |valor: Option<u64>| valor.map_or(String::new(), |v| v.to_string());
+        match (self.plazo, self.llegada) {
+            (plazo, Some(llegada)) => {
+                linea.push_str(&format!(",{},{}", opcional(plazo), llegada));
+            }
+            (Some(plazo), None) => linea.push_str(&format!(",{}", plazo)),
+            (None, None) => {}
+        }
+        linea
     }
 }
 
 /// Genera un archivo de pedidos con n o [`CANT_PEDIDOS`] pedidos aleatorios en la ruta dada.
+/// Para elegir la distribución de las llegadas, las bebidas o la semilla, utilizar
+/// [`Generador`](crate::generador::Generador).
+///
+/// # Errors
+/// * En caso de que n no sea un número, devuelve [`CafeteriaError::ArgumentosInvalidos`].
+/// * En caso de error al crear o escribir el archivo, devuelve [`CafeteriaError::CreacionArchivo`]
+///   o [`CafeteriaError::EscrituraArchivo`].
 pub fn generate_file(ruta: &str, n: Option<&String>) -> Result<(), CafeteriaError> {
     let n = n
-        .map(|n| n.parse::<usize>().unwrap_or(CANT_PEDIDOS))
+        .map(|n| n.parse::<usize>())
+        .transpose()
+        .map_err(|_| CafeteriaError::ArgumentosInvalidos)?
         .unwrap_or(CANT_PEDIDOS);
-    let pedidos: Vec<Pedido> = (1..=n).map(Pedido::new_random).collect();
-
-    let mut file = File::create(ruta).map_err(|_| CafeteriaError::CreacionArchivo)?;
-    for pedido in pedidos {
-        writeln!(file, "{}", pedido.to_line()).map_err(|_| CafeteriaError::EscrituraArchivo)?;
-    }
-    Ok(())
+    Generador::new(n).escribir(ruta)
 }
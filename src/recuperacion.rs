@@ -0,0 +1,93 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{LockResult, Mutex, MutexGuard, PoisonError, WaitTimeoutResult};
+
+use crate::error;
+
+/// Estado protegido por un lock que puede quedar inconsistente si un hilo entra en pánico
+/// mientras lo modifica.
+pub trait Reparable {
+    /// Corrige el estado para que vuelva a ser válido, devolviendo una descripción de cada
+    /// corrección realizada.
+    fn reparar(&mut self) -> Vec<String>;
+}
+
+/// Resultado de tomar un lock o de esperar en su [`Condvar`](std::sync::Condvar), que da acceso
+/// al estado protegido.
+pub trait Guardia<T> {
+    fn estado(&mut self) -> &mut T;
+}
+
+impl<T> Guardia<T> for MutexGuard<'_, T> {
+    fn estado(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T> Guardia<T> for (MutexGuard<'_, T>, WaitTimeoutResult) {
+    fn estado(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+/// Devuelve la guardia del resultado dado aunque el lock se encuentre envenenado. En ese caso
+/// repara el estado protegido, quita el envenenamiento del lock y registra el incidente, de
+/// forma que el pánico de un hilo no impida al resto seguir utilizando el lock.
+pub fn recuperar<T: Reparable, G: Guardia<T>>(
+    mutex: &Mutex<T>,
+    nombre: &str,
+    resultado: LockResult<G>,
+) -> G {
+    resultado.unwrap_or_else(|envenenado| {
+        let mut guardia = envenenado.into_inner();
+        let correcciones = guardia.estado().reparar();
+        mutex.clear_poison();
+        if correcciones.is_empty() {
+            error!("Lock del contenedor de {} envenenado, se recupera", nombre);
+        } else {
+            error!(
+                "Lock del contenedor de {} envenenado, se recupera corrigiendo {}",
+                nombre,
+                correcciones.join(", ")
+            );
+        }
+        guardia
+    })
+}
+
+/// Toma el lock dado, recuperándolo mediante [`recuperar`] si se encuentra envenenado.
+pub fn bloquear<'a, T: Reparable>(mutex: &'a Mutex<T>, nombre: &str) -> MutexGuard<'a, T> {
+    recuperar(mutex, nombre, mutex.lock())
+}
+
+/// Toma el lock dado aunque se encuentre envenenado, sin quitarle el envenenamiento. Sirve para
+/// leer contadores, que no quedan inconsistentes si un hilo entra en pánico mientras los modifica.
+pub fn leer<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Acota el nivel de un contenedor a su capacidad. Un nivel mayor a la mitad de [`u32::MAX`] se
+/// considera el resultado de restar por debajo de cero, y se lleva a cero. Devuelve la
+/// corrección realizada, si la hubo.
+pub(crate) fn acotar(nivel: &mut u32, capacidad: u32, nombre: &str) -> Option<String> {
+    if *nivel <= capacidad {
+        return None;
+    }
+    let corregido = if *nivel > u32::MAX / 2 { 0 } else { capacidad };
+    let correccion = format!("{} de {} a {}", nombre, nivel, corregido);
+    *nivel = corregido;
+    Some(correccion)
+}
+
+/// Ejecuta la función dada capturando un posible pánico, en cuyo caso devuelve su mensaje.
+///
+/// El estado compartido que la función pueda dejar a medio modificar se encuentra protegido
+/// por locks, que quedan envenenados y se recuperan al volver a tomarlos.
+pub fn aislar<F: FnOnce() -> T, T>(f: F) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|panico| {
+        panico
+            .downcast_ref::<&str>()
+            .map(|mensaje| (*mensaje).to_string())
+            .or_else(|| panico.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panico sin mensaje".to_string())
+    })
+}
@@ -0,0 +1,361 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cafetera::Cafetera;
+use crate::error::CafeteriaError;
+use crate::estadisticas::Estadisticas;
+use crate::tiempos::{Latencias, Percentiles, TiemposPedido};
+use crate::utilizacion::Utilizacion;
+
+/// Formato en el que se escribe un [`Reporte`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatoReporte {
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl FormatoReporte {
+    /// Determina el formato a partir de la extensión de la ruta: `.json`, `.csv` o `.md`.
+    ///
+    /// # Errors
+    /// * En caso de que la extensión no corresponda a ningún formato, devuelve [`CafeteriaError::FormatoInvalido`].
+    pub fn desde_ruta(ruta: &str) -> Result<FormatoReporte, CafeteriaError> {
+        let extension = Path::new(ruta)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("json") => Ok(FormatoReporte::Json),
+            Some("csv") => Ok(FormatoReporte::Csv),
+            Some("md") | Some("markdown") => Ok(FormatoReporte::Markdown),
+            _ => Err(CafeteriaError::FormatoInvalido),
+        }
+    }
+}
+
+/// Tiempos de un pedido completado, en milisegundos.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TiemposReporte {
+    pub id: usize,
+    pub dispensador: usize,
+    pub espera_dispensador_ms: f64,
+    pub espera_cafe_ms: f64,
+    pub espera_espuma_ms: f64,
+    pub total_ms: f64,
+}
+
+impl From<&TiemposPedido> for TiemposReporte {
+    fn from(t: &TiemposPedido) -> Self {
+        TiemposReporte {
+            id: t.id,
+            dispensador: t.dispensador,
+            espera_dispensador_ms: ms(t.espera_dispensador()),
+            espera_cafe_ms: ms(t.espera_cafe()),
+            espera_espuma_ms: ms(t.espera_espuma()),
+            total_ms: ms(t.total()),
+        }
+    }
+}
+
+/// [`Percentiles`] expresados en milisegundos.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PercentilesReporte {
+    pub media: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl From<Percentiles> for PercentilesReporte {
+    fn from(p: Percentiles) -> Self {
+        PercentilesReporte {
+            media: ms(p.media),
+            p50: ms(p.p50),
+            p95: ms(p.p95),
+            p99: ms(p.p99),
+            max: ms(p.max),
+        }
+    }
+}
+
+/// [`Latencias`] de todos los pedidos completados.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatenciasReporte {
+    pub espera_dispensador: Option<PercentilesReporte>,
+    pub espera_cafe: Option<PercentilesReporte>,
+    pub espera_espuma: Option<PercentilesReporte>,
+    pub total: Option<PercentilesReporte>,
+}
+
+impl From<Latencias> for LatenciasReporte {
+    fn from(l: Latencias) -> Self {
+        LatenciasReporte {
+            espera_dispensador: l.espera_dispensador.map(Into::into),
+            espera_cafe: l.espera_cafe.map(Into::into),
+            espera_espuma: l.espera_espuma.map(Into::into),
+            total: l.total.map(Into::into),
+        }
+    }
+}
+
+/// [`Utilizacion`] expresada en porcentajes de la duración de la corrida.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtilizacionReporte {
+    /// Porcentaje ocupado, bloqueado y libre de cada dispensador
+    pub dispensadores: Vec<(f64, f64, f64)>,
+    pub canilla_cafe: f64,
+    pub canilla_espuma: f64,
+    pub reposicion_cafe: f64,
+    pub reposicion_espuma: f64,
+    #[serde(default)]
+    pub pausa: f64,
+}
+
+impl From<&Utilizacion> for UtilizacionReporte {
+    fn from(u: &Utilizacion) -> Self {
+        UtilizacionReporte {
+            dispensadores: u
+                .dispensadores
+                .iter()
+                .map(|d| {
+                    (
+                        u.porcentaje(d.ocupado),
+                        u.porcentaje(d.bloqueado),
+                        u.porcentaje(d.libre),
+                    )
+                })
+                .collect(),
+            canilla_cafe: u.porcentaje(u.canilla_cafe),
+            canilla_espuma: u.porcentaje(u.canilla_espuma),
+            reposicion_cafe: u.porcentaje(u.reposicion_cafe),
+            reposicion_espuma: u.porcentaje(u.reposicion_espuma),
+            pausa: u.porcentaje(u.pausa),
+        }
+    }
+}
+
+/// Resumen de una corrida completa de la cafetera.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reporte {
+    /// Duración de la corrida en milisegundos
+    pub duracion_ms: f64,
+    /// Estado final de la cafetera
+    pub estadisticas: Estadisticas,
+    pub latencias: LatenciasReporte,
+    pub utilizacion: UtilizacionReporte,
+    /// Tiempos de cada pedido completado
+    pub pedidos: Vec<TiemposReporte>,
+}
+
+impl Reporte {
+    /// Genera el reporte de la corrida de la cafetera dada.
+    ///
+    /// # Errors
+    /// * En caso de que algún lock se encuentre envenenado, devuelve [`CafeteriaError::LockEnvenenado`].
+    pub fn generar(cafetera: &Cafetera) -> Result<Reporte, CafeteriaError> {
+        let estadisticas = cafetera.snapshot();
+        let utilizacion = cafetera.utilizacion()?;
+        let tiempos = cafetera.tiempos.lock()?;
+        Ok(Reporte {
+            duracion_ms: ms(utilizacion.duracion),
+            estadisticas,
+            latencias: Latencias::calcular(tiempos.iter()).into(),
+            utilizacion: (&utilizacion).into(),
+            pedidos: tiempos.iter().map(Into::into).collect(),
+        })
+    }
+
+    /// Lee un reporte escrito en formato JSON.
+    ///
+    /// # Errors
+    /// * En caso de que el archivo no tenga extensión `.json`, devuelve [`CafeteriaError::FormatoInvalido`].
+    /// * En caso de error al abrir el archivo, devuelve [`CafeteriaError::AperturaArchivo`].
+    /// * En caso de que el contenido no sea un reporte válido, devuelve [`CafeteriaError::LecturaArchivo`].
+    pub fn leer(ruta: &str) -> Result<Reporte, CafeteriaError> {
+        if FormatoReporte::desde_ruta(ruta)? != FormatoReporte::Json {
+            return Err(CafeteriaError::FormatoInvalido);
+        }
+        let file = File::open(ruta).map_err(|e| CafeteriaError::AperturaArchivo(e.into()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| CafeteriaError::LecturaArchivo(e.into()))
+    }
+
+    /// Pedidos completados por segundo.
+    #[must_use]
+    pub fn throughput(&self) -> f64 {
+        if self.duracion_ms <= 0.0 {
+            return 0.0;
+        }
+        f64::from(self.estadisticas.cant_pedidos) * 1000.0 / self.duracion_ms
+    }
+
+    /// Escribe el reporte en la ruta dada, en el formato correspondiente a su extensión.
+    ///
+    /// # Errors
+    /// * En caso de que la extensión no corresponda a ningún formato, devuelve [`CafeteriaError::FormatoInvalido`].
+    /// * En caso de error al crear el archivo, devuelve [`CafeteriaError::CreacionArchivo`].
+    /// * En caso de error al escribir el archivo, devuelve [`CafeteriaError::EscrituraArchivo`].
+    pub fn escribir(&self, ruta: &str) -> Result<(), CafeteriaError> {
+        let contenido = self.formatear(FormatoReporte::desde_ruta(ruta)?)?;
+        let mut file = File::create(ruta).map_err(|e| CafeteriaError::CreacionArchivo(e.into()))?;
+        file.write_all(contenido.as_bytes())
+            .map_err(|e| CafeteriaError::EscrituraArchivo(e.into()))
+    }
+
+    /// Devuelve el reporte en el formato dado.
+    ///
+    /// # Errors
+    /// * En caso de error al serializar el reporte, devuelve [`CafeteriaError::EscrituraArchivo`].
+    pub fn formatear(&self, formato: FormatoReporte) -> Result<String, CafeteriaError> {
+        match formato {
+            FormatoReporte::Json => serde_json::to_string_pretty(self)
+                .map(|json| json + "\n")
+                .map_err(|e| CafeteriaError::EscrituraArchivo(e.into())),
+            FormatoReporte::Csv => Ok(self.to_csv()),
+            FormatoReporte::Markdown => Ok(self.to_markdown()),
+        }
+    }
+
+    /// Pares de métrica y valor del resumen de la corrida.
+    fn resumen(&self) -> Vec<(String, String)> {
+        let e = &self.estadisticas;
+        let mut resumen: Vec<(String, String)> = vec![
+            ("duracion_ms".into(), format!("{:.0}", self.duracion_ms)),
+            ("pedidos_leidos".into(), e.pedidos_leidos.to_string()),
+            (
+                "pedidos_rechazados".into(),
+                e.pedidos_rechazados.to_string(),
+            ),
+            ("pedidos_repetidos".into(), e.pedidos_repetidos.to_string()),
+            ("locks_recuperados".into(), e.locks_recuperados.to_string()),
+            ("pedidos_completados".into(), e.cant_pedidos.to_string()),
+            ("pedidos_abortados".into(), e.pedidos_abortados.to_string()),
+            ("pedidos_fallidos".into(), e.pedidos_fallidos.to_string()),
+            (
+                "pedidos_cancelados".into(),
+                e.pedidos_cancelados.to_string(),
+            ),
+            ("agua_desperdiciada".into(), e.desperdicio.agua.to_string()),
+            ("cafe_desperdiciado".into(), e.desperdicio.cafe.to_string()),
+            (
+                "espuma_desperdiciada".into(),
+                e.desperdicio.espuma.to_string(),
+            ),
+            ("throughput".into(), format!("{:.3}", self.throughput())),
+            ("cafe_consumido".into(), e.cafe_consumido.to_string()),
+            ("granos_consumidos".into(), e.granos_consumidos.to_string()),
+            ("espuma_consumida".into(), e.espuma_consumida.to_string()),
+            ("leche_consumida".into(), e.leche_consumida.to_string()),
+            ("reposiciones_cafe".into(), e.reposiciones_cafe.to_string()),
+            (
+                "reposiciones_espuma".into(),
+                e.reposiciones_espuma.to_string(),
+            ),
+        ];
+        let latencias = [
+            ("espera_dispensador", self.latencias.espera_dispensador),
+            ("espera_cafe", self.latencias.espera_cafe),
+            ("espera_espuma", self.latencias.espera_espuma),
+            ("total", self.latencias.total),
+        ];
+        for (nombre, p) in latencias {
+            if let Some(p) = p {
+                for (percentil, valor) in [
+                    ("media", p.media),
+                    ("p50", p.p50),
+                    ("p95", p.p95),
+                    ("p99", p.p99),
+                    ("max", p.max),
+                ] {
+                    resumen.push((
+                        format!("{}_{}_ms", nombre, percentil),
+                        format!("{:.1}", valor),
+                    ));
+                }
+            }
+        }
+        let u = &self.utilizacion;
+        for (i, (ocupado, bloqueado, libre)) in u.dispensadores.iter().enumerate() {
+            resumen.push((
+                format!("dispensador_{}_ocupado_pct", i),
+                format!("{:.1}", ocupado),
+            ));
+            resumen.push((
+                format!("dispensador_{}_bloqueado_pct", i),
+                format!("{:.1}", bloqueado),
+            ));
+            resumen.push((
+                format!("dispensador_{}_libre_pct", i),
+                format!("{:.1}", libre),
+            ));
+        }
+        for (nombre, valor) in [
+            ("canilla_cafe_pct", u.canilla_cafe),
+            ("canilla_espuma_pct", u.canilla_espuma),
+            ("reposicion_cafe_pct", u.reposicion_cafe),
+            ("reposicion_espuma_pct", u.reposicion_espuma),
+            ("pausa_pct", u.pausa),
+        ] {
+            resumen.push((nombre.into(), format!("{:.1}", valor)));
+        }
+        resumen
+    }
+
+    /// Tabla de métricas seguida, luego de una línea vacía, de la tabla de pedidos.
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("metrica,valor\n");
+        for (metrica, valor) in self.resumen() {
+            let _ = writeln!(csv, "{},{}", metrica, valor);
+        }
+        csv.push_str(
+            "\nid,dispensador,espera_dispensador_ms,espera_cafe_ms,espera_espuma_ms,total_ms\n",
+        );
+        for p in &self.pedidos {
+            let _ = writeln!(
+                csv,
+                "{},{},{:.1},{:.1},{:.1},{:.1}",
+                p.id,
+                p.dispensador,
+                p.espera_dispensador_ms,
+                p.espera_cafe_ms,
+                p.espera_espuma_ms,
+                p.total_ms
+            );
+        }
+        csv
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut md = String::from(
+            "# Reporte de la corrida\n\n## Resumen\n\n| Métrica | Valor |\n|---|---|\n",
+        );
+        for (metrica, valor) in self.resumen() {
+            let _ = writeln!(md, "| {} | {} |", metrica, valor);
+        }
+        md.push_str("\n## Pedidos\n\n| Id | Dispensador | Espera dispensador (ms) | Espera café (ms) | Espera espuma (ms) | Total (ms) |\n|---|---|---|---|---|---|\n");
+        for p in &self.pedidos {
+            let _ = writeln!(
+                md,
+                "| {} | {} | {:.1} | {:.1} | {:.1} | {:.1} |",
+                p.id,
+                p.dispensador,
+                p.espera_dispensador_ms,
+                p.espera_cafe_ms,
+                p.espera_espuma_ms,
+                p.total_ms
+            );
+        }
+        md
+    }
+}
+
+fn ms(duracion: Duration) -> f64 {
+    duracion.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Instantes por los que pasa un pedido desde su llegada hasta completarse.
+#[derive(Debug, Clone, Copy)]
+pub struct TiemposPedido {
+    pub id: usize,
+    /// Dispensador en el que se preparó el pedido
+    pub dispensador: usize,
+    /// Lectura del pedido
+    pub llegada: Instant,
+    /// Obtención del dispensador
+    pub asignacion: Instant,
+    /// Inicio y fin del servido de agua
+    pub agua: (Instant, Instant),
+    /// Inicio y fin del servido de café
+    pub cafe: (Instant, Instant),
+    /// Inicio y fin del servido de espuma
+    pub espuma: (Instant, Instant),
+    /// Finalización del pedido
+    pub fin: Instant,
+}
+
+impl TiemposPedido {
+    /// Tiempo de espera hasta obtener un dispensador.
+    #[must_use]
+    pub fn espera_dispensador(&self) -> Duration {
+        self.asignacion.saturating_duration_since(self.llegada)
+    }
+
+    /// Tiempo de espera entre terminar el agua y comenzar a servir el café.
+    #[must_use]
+    pub fn espera_cafe(&self) -> Duration {
+        self.cafe.0.saturating_duration_since(self.agua.1)
+    }
+
+    /// Tiempo de espera entre terminar el café y comenzar a servir la espuma.
+    #[must_use]
+    pub fn espera_espuma(&self) -> Duration {
+        self.espuma.0.saturating_duration_since(self.cafe.1)
+    }
+
+    /// Tiempo desde la llegada hasta la finalización del pedido.
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.fin.saturating_duration_since(self.llegada)
+    }
+}
+
+/// Media, percentiles 50, 95 y 99, y máximo de un conjunto de duraciones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Percentiles {
+    pub media: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Percentiles {
+    /// Calcula los percentiles por el método del rango más cercano.
+    /// Devuelve `None` si no hay muestras.
+    #[must_use]
+    pub fn calcular(mut muestras: Vec<Duration>) -> Option<Percentiles> {
+        if muestras.is_empty() {
+            return None;
+        }
+        muestras.sort();
+        let n = muestras.len();
+        let rango = |p: usize| muestras[(p * n).div_ceil(100).max(1) - 1];
+        let total: Duration = muestras.iter().sum();
+        Some(Percentiles {
+            media: total / n as u32,
+            p50: rango(50),
+            p95: rango(95),
+            p99: rango(99),
+            max: muestras[n - 1],
+        })
+    }
+}
+
+impl fmt::Display for Percentiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "media {}ms, p50 {}ms, p95 {}ms, p99 {}ms, max {}ms",
+            self.media.as_millis(),
+            self.p50.as_millis(),
+            self.p95.as_millis(),
+            self.p99.as_millis(),
+            self.max.as_millis()
+        )
+    }
+}
+
+/// Latencias de un conjunto de pedidos completados.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Latencias {
+    /// Cantidad de pedidos considerados
+    pub pedidos: usize,
+    pub espera_dispensador: Option<Percentiles>,
+    pub espera_cafe: Option<Percentiles>,
+    pub espera_espuma: Option<Percentiles>,
+    pub total: Option<Percentiles>,
+}
+
+impl Latencias {
+    /// Calcula las latencias de los pedidos dados.
+    #[must_use]
+    pub fn calcular<'a, I>(tiempos: I) -> Latencias
+    where
+        I: IntoIterator<Item = &'a TiemposPedido>,
+    {
+        let tiempos: Vec<&TiemposPedido> = tiempos.into_iter().collect();
+        let muestras = |f: fn(&TiemposPedido) -> Duration| {
+            Percentiles::calcular(tiempos.iter().map(|t| f(t)).collect())
+        };
+        Latencias {
+            pedidos: tiempos.len(),
+            espera_dispensador: muestras(TiemposPedido::espera_dispensador),
+            espera_cafe: muestras(TiemposPedido::espera_cafe),
+            espera_espuma: muestras(TiemposPedido::espera_espuma),
+            total: muestras(TiemposPedido::total),
+        }
+    }
+
+    /// Calcula las latencias de los pedidos preparados en el dispensador dado.
+    #[must_use]
+    pub fn por_dispensador(tiempos: &[TiemposPedido], dispensador: usize) -> Latencias {
+        Latencias::calcular(tiempos.iter().filter(|t| t.dispensador == dispensador))
+    }
+
+    /// Líneas con cada una de las latencias que tengan muestras.
+    #[must_use]
+    pub fn lineas(&self) -> Vec<String> {
+        [
+            ("Espera por dispensador", self.espera_dispensador),
+            ("Espera por cafe", self.espera_cafe),
+            ("Espera por espuma", self.espera_espuma),
+            ("Tiempo total", self.total),
+        ]
+        .iter()
+        .filter_map(|(nombre, p)| p.map(|p| format!("{}: {}", nombre, p)))
+        .collect()
+    }
+}
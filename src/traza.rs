@@ -0,0 +1,178 @@
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::constantes::N;
+use crate::error::CafeteriaError;
+
+/// Pista de la línea de tiempo en la que se registra un intervalo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pista {
+    /// Dispensador con el número dado
+    Dispensador(usize),
+    /// Hilo de reposición de café molido
+    RellenoCafe,
+    /// Hilo de reposición de espuma
+    RellenoEspuma,
+    /// Estado general de la cafetera, como sus pausas
+    Cafetera,
+}
+
+impl Pista {
+    /// Identificador del hilo en el formato Chrome Trace Event.
+    fn tid(self) -> usize {
+        match self {
+            Pista::Dispensador(i) => i,
+            Pista::RellenoCafe => N,
+            Pista::RellenoEspuma => N + 1,
+            Pista::Cafetera => N + 2,
+        }
+    }
+
+    /// Nombre con el que se muestra la pista en el visor.
+    fn nombre(self) -> String {
+        match self {
+            Pista::Dispensador(i) => format!("Dispensador {}", i),
+            Pista::RellenoCafe => "Reposicion cafe".to_string(),
+            Pista::RellenoEspuma => "Reposicion espuma".to_string(),
+            Pista::Cafetera => "Cafetera".to_string(),
+        }
+    }
+}
+
+enum Evento {
+    Intervalo {
+        pista: Pista,
+        nombre: &'static str,
+        pedido: Option<usize>,
+        inicio: u128,
+        duracion: u128,
+    },
+    Contador {
+        nombre: &'static str,
+        ts: u128,
+        valor: u32,
+    },
+}
+
+/// Registro de los eventos de una corrida para exportarlos en formato Chrome Trace Event,
+/// legible por `chrome://tracing` o [Perfetto](https://ui.perfetto.dev).
+///
+/// Los tiempos se miden en microsegundos desde la creación de la traza.
+pub struct Traza {
+    inicio: Instant,
+    eventos: Mutex<Vec<Evento>>,
+}
+
+impl Traza {
+    #[must_use]
+    pub fn new() -> Traza {
+        Traza {
+            inicio: Instant::now(),
+            eventos: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn micros(&self, instante: Instant) -> u128 {
+        instante.saturating_duration_since(self.inicio).as_micros()
+    }
+
+    /// Registra un intervalo entre desde y hasta en la pista dada.
+    pub fn intervalo(
+        &self,
+        pista: Pista,
+        nombre: &'static str,
+        pedido: Option<usize>,
+        desde: Instant,
+        hasta: Instant,
+    ) {
+        let inicio = self.micros(desde);
+        let duracion = self.micros(hasta).saturating_sub(inicio);
+        if let Ok(mut eventos) = self.eventos.lock() {
+            eventos.push(Evento::Intervalo {
+                pista,
+                nombre,
+                pedido,
+                inicio,
+                duracion,
+            });
+        }
+    }
+
+    /// Registra el valor actual de un contenedor.
+    pub fn contador(&self, nombre: &'static str, valor: u32) {
+        let ts = self.micros(Instant::now());
+        if let Ok(mut eventos) = self.eventos.lock() {
+            eventos.push(Evento::Contador { nombre, ts, valor });
+        }
+    }
+
+    /// Serializa la traza como un arreglo JSON de eventos.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let pistas = (0..N).map(Pista::Dispensador).chain([
+            Pista::RellenoCafe,
+            Pista::RellenoEspuma,
+            Pista::Cafetera,
+        ]);
+        let mut lineas: Vec<String> = pistas
+            .map(|pista| {
+                format!(
+                    "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+                    pista.tid(),
+                    pista.nombre()
+                )
+            })
+            .collect();
+
+        if let Ok(eventos) = self.eventos.lock() {
+            for evento in eventos.iter() {
+                lineas.push(match evento {
+                    Evento::Intervalo {
+                        pista,
+                        nombre,
+                        pedido,
+                        inicio,
+                        duracion,
+                    } => {
+                        let args = pedido
+                            .map(|id| format!(",\"args\":{{\"pedido\":{}}}", id))
+                            .unwrap_or_default();
+                        format!(
+                            "{{\"name\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{},\"dur\":{}{}}}",
+                            nombre,
+                            pista.tid(),
+                            inicio,
+                            duracion,
+                            args
+                        )
+                    }
+                    Evento::Contador { nombre, ts, valor } => format!(
+                        "{{\"name\":\"{0}\",\"ph\":\"C\",\"pid\":1,\"ts\":{1},\"args\":{{\"{0}\":{2}}}}}",
+                        nombre, ts, valor
+                    ),
+                });
+            }
+        }
+
+        format!("[\n{}\n]\n", lineas.join(",\n"))
+    }
+
+    /// Escribe la traza en la ruta dada.
+    ///
+    /// # Errors
+    /// * En caso de error al crear el archivo, devuelve [`CafeteriaError::CreacionArchivo`].
+    /// * En caso de error al escribir el archivo, devuelve [`CafeteriaError::EscrituraArchivo`].
+    pub fn escribir(&self, ruta: &str) -> Result<(), CafeteriaError> {
+        let mut file = File::create(ruta).map_err(|e| CafeteriaError::CreacionArchivo(e.into()))?;
+        file.write_all(self.to_json().as_bytes())
+            .map_err(|e| CafeteriaError::EscrituraArchivo(e.into()))
+    }
+}
+
+impl Default for Traza {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+/// Instantes de inicio y fin de un intervalo de tiempo.
+pub type Intervalo = (Instant, Instant);
+
+/// Intervalo en que un dispensador estuvo asignado a un pedido, cualquiera sea su resultado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ocupacion {
+    pub dispensador: usize,
+    /// Instante en que se asignó el dispensador al pedido
+    pub asignacion: Instant,
+    /// Instante en que se liberó el dispensador
+    pub liberacion: Instant,
+    /// Tiempo esperando por café o espuma
+    pub bloqueado: Duration,
+    /// Tiempo sirviendo café
+    pub cafe: Duration,
+    /// Tiempo sirviendo espuma
+    pub espuma: Duration,
+}
+
+/// Distribución del tiempo de un dispensador durante la corrida.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UtilizacionDispensador {
+    /// Tiempo preparando pedidos
+    pub ocupado: Duration,
+    /// Tiempo con un pedido asignado esperando por café o espuma
+    pub bloqueado: Duration,
+    /// Tiempo sin pedido asignado y con la cafetera sin pausar. El tiempo pausado con un pedido
+    /// asignado se cuenta como ocupado o bloqueado
+    pub libre: Duration,
+}
+
+/// Utilización de los dispensadores, las canillas de café y espuma, y los contenedores
+/// durante una corrida.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utilizacion {
+    /// Duración de la corrida
+    pub duracion: Duration,
+    pub dispensadores: Vec<UtilizacionDispensador>,
+    /// Tiempo sirviendo café
+    pub canilla_cafe: Duration,
+    /// Tiempo sirviendo espuma
+    pub canilla_espuma: Duration,
+    /// Tiempo reponiendo el contenedor de café molido
+    pub reposicion_cafe: Duration,
+    /// Tiempo reponiendo el contenedor de espuma
+    pub reposicion_espuma: Duration,
+    /// Tiempo con la cafetera pausada
+    pub pausa: Duration,
+}
+
+impl Utilizacion {
+    /// Calcula la utilización de una corrida de la duración dada con la cantidad de dispensadores
+    /// dada a partir de las ocupaciones de los dispensadores, que incluyen a los pedidos que no
+    /// se completaron. El tiempo de las pausas dadas no se cuenta como tiempo libre de los
+    /// dispensadores.
+    #[must_use]
+    pub fn calcular(
+        duracion: Duration,
+        dispensadores: usize,
+        ocupaciones: &[Ocupacion],
+        reposicion_cafe: Duration,
+        reposicion_espuma: Duration,
+        pausas: &[Intervalo],
+    ) -> Utilizacion {
+        let pausa = duracion_total(pausas);
+        let dispensadores = (0..dispensadores)
+            .map(|i| {
+                let (mut asignado, mut bloqueado) = (Duration::ZERO, Duration::ZERO);
+                let mut pausa_asignado = Duration::ZERO;
+                for o in ocupaciones.iter().filter(|o| o.dispensador == i) {
+                    asignado += o.liberacion.saturating_duration_since(o.asignacion);
+                    bloqueado += o.bloqueado;
+                    for &(desde, hasta) in pausas {
+                        pausa_asignado += hasta
+                            .min(o.liberacion)
+                            .saturating_duration_since(desde.max(o.asignacion));
+                    }
+                }
+                UtilizacionDispensador {
+                    ocupado: asignado.saturating_sub(bloqueado),
+                    bloqueado: bloqueado.min(asignado),
+                    libre: duracion
+                        .saturating_sub(asignado)
+                        .saturating_sub(pausa.saturating_sub(pausa_asignado)),
+                }
+            })
+            .collect();
+        Utilizacion {
+            duracion,
+            dispensadores,
+            canilla_cafe: ocupaciones.iter().map(|o| o.cafe).sum(),
+            canilla_espuma: ocupaciones.iter().map(|o| o.espuma).sum(),
+            reposicion_cafe,
+            reposicion_espuma,
+            pausa,
+        }
+    }
+
+    /// Porcentaje de la duración de la corrida que representa el tiempo dado.
+    #[must_use]
+    pub fn porcentaje(&self, tiempo: Duration) -> f64 {
+        if self.duracion.is_zero() {
+            return 0.0;
+        }
+        tiempo.as_secs_f64() * 100.0 / self.duracion.as_secs_f64()
+    }
+
+    /// Líneas con los porcentajes de utilización de cada recurso.
+    #[must_use]
+    pub fn lineas(&self) -> Vec<String> {
+        let mut lineas: Vec<String> = self
+            .dispensadores
+            .iter()
+            .enumerate()
+            .map(|(i, d)| {
+                format!(
+                    "Dispensador {}: ocupado {:.1}%, bloqueado {:.1}%, libre {:.1}%",
+                    i,
+                    self.porcentaje(d.ocupado),
+                    self.porcentaje(d.bloqueado),
+                    self.porcentaje(d.libre)
+                )
+            })
+            .collect();
+        lineas.push(format!(
+            "Canillas: cafe {:.1}%, espuma {:.1}%",
+            self.porcentaje(self.canilla_cafe),
+            self.porcentaje(self.canilla_espuma)
+        ));
+        lineas.push(format!(
+            "Reposicion: cafe {:.1}%, espuma {:.1}%",
+            self.porcentaje(self.reposicion_cafe),
+            self.porcentaje(self.reposicion_espuma)
+        ));
+        lineas.push(format!("Pausa: {:.1}%", self.porcentaje(self.pausa)));
+        lineas
+    }
+}
+
+/// Suma de las duraciones de los intervalos dados.
+#[must_use]
+pub fn duracion_total(intervalos: &[Intervalo]) -> Duration {
+    intervalos
+        .iter()
+        .map(|(desde, hasta)| hasta.saturating_duration_since(*desde))
+        .sum()
+}
@@ -0,0 +1,150 @@
+use std::cmp::min;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::constantes::{C, E, G, L, MAX_CANTIDAD};
+use crate::error::CafeteriaError;
+use crate::lectura::{Entrada, Entradas, FormatoPedidos, Rechazo};
+use crate::pedido::Linea;
+
+/// Consumo estimado de uno de los productos de la cafetera, simulando su reposición con las
+/// mismas reglas que los hilos de reposición.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Consumo {
+    /// Cantidad servida a los pedidos
+    pub servido: u32,
+    /// Cantidad de granos o leche utilizada para reponer el contenedor
+    pub consumido: u32,
+    /// Cantidad de reposiciones del contenedor de café molido o espuma
+    pub reposiciones: u32,
+    /// Cantidad de veces que se recargan los granos o la leche
+    pub recargas: u32,
+}
+
+impl Consumo {
+    /// Estima el consumo de las cantidades dadas, en orden, para un contenedor de la capacidad
+    /// dada que se repone desde una reserva de la capacidad dada.
+    #[must_use]
+    pub fn estimar(cantidades: &[u32], capacidad: u32, reserva: u32) -> Consumo {
+        let mut consumo = Consumo::default();
+        let (mut nivel, mut disponible) = (0, reserva);
+        for &cantidad in cantidades {
+            while nivel < MAX_CANTIDAD {
+                let repuesto = min(capacidad - nivel, disponible);
+                nivel += repuesto;
+                disponible -= repuesto;
+                consumo.consumido += repuesto;
+                consumo.reposiciones += 1;
+                if disponible < capacidad {
+                    disponible = reserva;
+                    consumo.recargas += 1;
+                }
+            }
+            nivel -= cantidad;
+            consumo.servido += cantidad;
+        }
+        consumo
+    }
+}
+
+/// Resultado de validar un archivo de pedidos sin correr la cafetera.
+#[derive(Debug, Default)]
+pub struct Validacion {
+    /// Cantidad de pedidos válidos
+    pub pedidos: u32,
+    /// Cantidad de cancelaciones
+    pub cancelaciones: u32,
+    /// Entradas que no son pedidos ni cancelaciones válidos
+    pub rechazados: Vec<Rechazo>,
+    /// Ids repetidos, una vez por cada repetición
+    pub duplicados: Vec<usize>,
+    /// Consumo estimado de café y granos
+    pub cafe: Consumo,
+    /// Consumo estimado de espuma y leche
+    pub espuma: Consumo,
+}
+
+impl Validacion {
+    /// Valida el archivo de pedidos de la ruta dada en el formato dado.
+    ///
+    /// # Errors
+    /// * En caso de error al abrir el archivo, devuelve [`CafeteriaError::AperturaArchivo`].
+    /// * En caso de error al leer el archivo, devuelve [`CafeteriaError::LecturaArchivo`].
+    /// * En caso de que un archivo JSON o YAML no contenga un arreglo de pedidos, devuelve [`CafeteriaError::FormatoInvalido`].
+    pub fn validar(ruta: &str, formato: FormatoPedidos) -> Result<Validacion, CafeteriaError> {
+        let file = File::open(ruta).map_err(|e| CafeteriaError::AperturaArchivo(e.into()))?;
+        Self::desde_entradas(formato.entradas(BufReader::new(file))?)
+    }
+
+    /// Valida las entradas de un archivo de pedidos.
+    ///
+    /// # Errors
+    /// * En caso de error al leer el archivo, devuelve [`CafeteriaError::LecturaArchivo`].
+    pub fn desde_entradas(entradas: Entradas) -> Result<Validacion, CafeteriaError> {
+        let mut validacion = Validacion::default();
+        let mut ids = HashSet::new();
+        let (mut cafe, mut espuma) = (Vec::new(), Vec::new());
+        for Entrada { texto, resultado } in entradas {
+            match resultado {
+                Ok(Linea::Pedido(pedido)) => {
+                    if !ids.insert(pedido.id) {
+                        validacion.duplicados.push(pedido.id);
+                    }
+                    validacion.pedidos += 1;
+                    cafe.push(pedido.cafe);
+                    espuma.push(pedido.espuma);
+                }
+                Ok(Linea::Cancelacion(_)) => validacion.cancelaciones += 1,
+                Err(error @ CafeteriaError::LecturaArchivo(_)) => return Err(error),
+                Err(error) => validacion.rechazados.push(Rechazo { texto, error }),
+            }
+        }
+        validacion.cafe = Consumo::estimar(&cafe, C, G);
+        validacion.espuma = Consumo::estimar(&espuma, E, L);
+        Ok(validacion)
+    }
+
+    /// Devuelve true si no hay entradas rechazadas ni ids repetidos.
+    #[must_use]
+    pub fn es_valida(&self) -> bool {
+        self.rechazados.is_empty() && self.duplicados.is_empty()
+    }
+
+    /// Resumen de la validación, con una línea por cada problema encontrado.
+    #[must_use]
+    pub fn lineas(&self) -> Vec<String> {
+        let mut lineas: Vec<String> = self
+            .rechazados
+            .iter()
+            .map(|rechazo| format!("Pedido invalido: {}", rechazo.descripcion()))
+            .collect();
+        lineas.extend(
+            self.duplicados
+                .iter()
+                .map(|id| format!("Pedido {} repetido", id)),
+        );
+        lineas.extend([
+            format!(
+                "Pedidos validos: {}, rechazados: {}, repetidos: {}, cancelaciones: {}",
+                self.pedidos,
+                self.rechazados.len(),
+                self.duplicados.len(),
+                self.cancelaciones
+            ),
+            format!(
+                "Consumo estimado: cafe {}, granos {}, espuma {}, leche {}",
+                self.cafe.servido, self.cafe.consumido, self.espuma.servido, self.espuma.consumido
+            ),
+            format!(
+                "Reposiciones estimadas: cafe {} (capacidad {}), espuma {} (capacidad {})",
+                self.cafe.reposiciones, C, self.espuma.reposiciones, E
+            ),
+            format!(
+                "Recargas estimadas: granos {} (capacidad {}), leche {} (capacidad {})",
+                self.cafe.recargas, G, self.espuma.recargas, L
+            ),
+        ]);
+        lineas
+    }
+}
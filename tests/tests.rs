@@ -1,9 +1,35 @@
 #[cfg(test)]
 mod tests {
+    use cafeteria::bebidas::Bebida;
     use cafeteria::cafetera::Cafetera;
-    use cafeteria::constantes::{C, E, G, L};
-    use cafeteria::error::CafeteriaError;
+    use cafeteria::cli::{self, Comando, Subcomando};
+    use cafeteria::comparacion::Comparacion;
+    use cafeteria::configuracion::{Configuracion, ModoApagado, PoliticaRepetidos};
+    use cafeteria::constantes::{C, E, G, L, N, PRECIO_PEDIDO, TIEMPO_CAFE, TIEMPO_ESPUMA};
+    use cafeteria::error::{
+        CafeteriaError, DetallePedido, Motivo, SALIDA_ARCHIVO, SALIDA_ARGUMENTOS,
+        SALIDA_LOCK_ENVENENADO, SALIDA_PEDIDOS_INVALIDOS,
+    };
+    use cafeteria::estadisticas::{Desperdicio, Estadisticas, EstadoDispensador};
+    use cafeteria::generador::{Generador, Mezcla, ModeloLlegadas};
+    use cafeteria::lectura::{
+        escribir_rechazados, Entrada, FormatoPedidos, LectorPedidos, Rechazo,
+    };
+    use cafeteria::log::{self, Nivel};
+    use cafeteria::metricas::ServidorMetricas;
+    use cafeteria::pedido::{Cancelacion, Linea, Pedido, Resultado};
+    use cafeteria::recuperacion::aislar;
+    use cafeteria::reporte::{FormatoReporte, Reporte};
+    use cafeteria::tiempos::{Latencias, Percentiles};
+    use cafeteria::utilizacion::UtilizacionDispensador;
+    use cafeteria::validacion::{Consumo, Validacion};
+    use std::error::Error;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
 
+    #[allow(clippy::too_many_arguments)]
     fn assert_estado_cafetera(
         ruta: &str,
         pedidos: u32,
@@ -18,19 +44,17 @@ mod tests {
     ) {
         let cafetera = Cafetera::new();
         let res = cafetera.realizar_pedidos(ruta);
-        let cant_pedidos = *cafetera.cant_pedidos.lock().unwrap();
-        let contenedor_cafe = cafetera.cafe.0.lock().unwrap();
-        let contenedor_espuma = cafetera.espuma.0.lock().unwrap();
+        let estadisticas = cafetera.snapshot();
         assert!(res.is_ok());
-        assert_eq!(cant_pedidos, pedidos);
-        assert_eq!(contenedor_cafe.cafe_consumido, cafe_cons);
-        assert_eq!(contenedor_cafe.granos_consumidos, granos_cons);
-        assert_eq!(contenedor_cafe.cafe_molido, cafe);
-        assert_eq!(contenedor_cafe.granos, granos);
-        assert_eq!(contenedor_espuma.espuma_consumida, espuma_cons);
-        assert_eq!(contenedor_espuma.leche_consumida, leche_cons);
-        assert_eq!(contenedor_espuma.espuma, espuma);
-        assert_eq!(contenedor_espuma.leche, leche);
+        assert_eq!(estadisticas.cant_pedidos, pedidos);
+        assert_eq!(estadisticas.cafe_consumido, cafe_cons);
+        assert_eq!(estadisticas.granos_consumidos, granos_cons);
+        assert_eq!(estadisticas.cafe_molido, cafe);
+        assert_eq!(estadisticas.granos, granos);
+        assert_eq!(estadisticas.espuma_consumida, espuma_cons);
+        assert_eq!(estadisticas.leche_consumida, leche_cons);
+        assert_eq!(estadisticas.espuma, espuma);
+        assert_eq!(estadisticas.leche, leche);
     }
 
     #[test]
@@ -38,16 +62,16 @@ mod tests {
         let cafetera = Cafetera::new();
         let res = cafetera.realizar_pedidos("");
         assert!(res.is_err());
-        assert_eq!(res.unwrap_err(), CafeteriaError::AperturaArchivo);
+        assert!(matches!(res, Err(CafeteriaError::AperturaArchivo(_))));
     }
 
     #[test]
     fn test02_cero_pedidos() {
         let cafetera = Cafetera::new();
         let res = cafetera.realizar_pedidos("tests/test02.txt");
-        let cant_pedidos = *cafetera.cant_pedidos.lock().unwrap();
+        let estadisticas = cafetera.snapshot();
         assert!(res.is_ok());
-        assert_eq!(cant_pedidos, 0);
+        assert_eq!(estadisticas.cant_pedidos, 0);
     }
 
     #[test]
@@ -75,4 +99,992 @@ mod tests {
     fn test05_pedidos_invalidos() {
         assert_estado_cafetera("tests/test05.txt", 2, 40, 150, 38, 150, 10, 50, 12, 50);
     }
+
+    #[test]
+    fn test06_traza() {
+        let cafetera = Cafetera::new();
+        let res = cafetera.realizar_pedidos("tests/test03.txt");
+        let traza = cafetera.traza.to_json();
+        assert!(res.is_ok());
+        assert!(traza.contains("\"name\":\"Dispensador 0\""));
+        for paso in ["agua", "cafe", "espuma", "reposicion"] {
+            assert!(traza.contains(&format!("\"name\":\"{}\",\"ph\":\"X\"", paso)));
+        }
+        for contador in ["cafe_molido", "granos", "espuma", "leche"] {
+            assert!(traza.contains(&format!("\"name\":\"{}\",\"ph\":\"C\"", contador)));
+        }
+    }
+
+    #[test]
+    fn test07_percentiles() {
+        let muestras = (1..=100).map(Duration::from_millis).collect();
+        let percentiles = Percentiles::calcular(muestras).unwrap();
+        assert_eq!(percentiles.media, Duration::from_micros(50500));
+        assert_eq!(percentiles.p50, Duration::from_millis(50));
+        assert_eq!(percentiles.p95, Duration::from_millis(95));
+        assert_eq!(percentiles.p99, Duration::from_millis(99));
+        assert_eq!(percentiles.max, Duration::from_millis(100));
+        assert!(Percentiles::calcular(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test08_latencias_pedidos() {
+        let cafetera = Cafetera::new();
+        let res = cafetera.realizar_pedidos("tests/test05.txt");
+        let tiempos = cafetera.tiempos.lock().unwrap();
+        let latencias = Latencias::calcular(tiempos.iter());
+        assert!(res.is_ok());
+        assert_eq!(latencias.pedidos, 2);
+        assert!(latencias.total.unwrap().max >= Duration::from_millis(2000));
+        let por_dispensador: usize = (0..N)
+            .map(|i| Latencias::por_dispensador(&tiempos, i).pedidos)
+            .sum();
+        assert_eq!(por_dispensador, 2);
+    }
+
+    #[test]
+    fn test09_utilizacion() {
+        let cafetera = Cafetera::new();
+        let res = cafetera.realizar_pedidos("tests/test03.txt");
+        let utilizacion = cafetera.utilizacion().unwrap();
+        assert!(res.is_ok());
+        assert_eq!(utilizacion.dispensadores.len(), N);
+        for dispensador in &utilizacion.dispensadores {
+            let total = dispensador.ocupado + dispensador.bloqueado + dispensador.libre;
+            assert_eq!(total, utilizacion.duracion);
+        }
+        assert!(utilizacion.canilla_cafe >= Duration::from_millis(500));
+        assert!(utilizacion.canilla_espuma >= Duration::from_millis(500));
+        assert!(utilizacion.reposicion_cafe >= Duration::from_millis(TIEMPO_CAFE));
+        assert!(utilizacion.reposicion_espuma >= Duration::from_millis(TIEMPO_ESPUMA));
+    }
+
+    #[test]
+    fn test10_metricas() {
+        let cafetera = Cafetera::new();
+        let servidor = ServidorMetricas::iniciar(cafetera.clone(), "127.0.0.1:0").unwrap();
+        let corrida = cafetera.clone();
+        let handle = thread::spawn(move || corrida.realizar_pedidos("tests/test03.txt"));
+
+        let mut stream = TcpStream::connect(servidor.direccion()).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut respuesta = String::new();
+        stream.read_to_string(&mut respuesta).unwrap();
+        // Un cliente que no envía nada no debe impedir que el servidor finalice
+        let _inactivo = TcpStream::connect(servidor.direccion()).unwrap();
+
+        assert!(handle.join().unwrap().is_ok());
+        servidor.esperar();
+        assert!(respuesta.starts_with("HTTP/1.1 200 OK"));
+        assert!(respuesta.contains("cafeteria_contenedor{contenedor=\"granos\"}"));
+        assert!(respuesta.contains("cafeteria_pedidos_completados_total 0"));
+        assert!(respuesta.contains("# TYPE cafeteria_pedido_segundos histogram"));
+    }
+
+    #[test]
+    fn test11_snapshot_serializable() {
+        let cafetera = Cafetera::new();
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(
+            estadisticas.dispensadores,
+            vec![EstadoDispensador::Libre; N]
+        );
+        assert_eq!(estadisticas.granos, G);
+        assert_eq!(estadisticas.leche, L);
+
+        let json = serde_json::to_string(&estadisticas).unwrap();
+        assert!(json.contains("\"dispensadores\":[\"libre\""));
+        let leidas: Estadisticas = serde_json::from_str(&json).unwrap();
+        assert_eq!(leidas, estadisticas);
+    }
+
+    #[test]
+    fn test12_reporte() {
+        let cafetera = Cafetera::new();
+        let res = cafetera.realizar_pedidos("tests/test05.txt");
+        let reporte = Reporte::generar(&cafetera).unwrap();
+        assert!(res.is_ok());
+        assert_eq!(reporte.estadisticas.pedidos_leidos, 6);
+        assert_eq!(reporte.estadisticas.pedidos_rechazados, 4);
+        assert_eq!(reporte.estadisticas.cant_pedidos, 2);
+        assert_eq!(reporte.pedidos.len(), 2);
+
+        let json = reporte.formatear(FormatoReporte::Json).unwrap();
+        let leido: Reporte = serde_json::from_str(&json).unwrap();
+        assert_eq!(leido.estadisticas, reporte.estadisticas);
+        assert_eq!(leido.pedidos.len(), reporte.pedidos.len());
+        let csv = reporte.formatear(FormatoReporte::Csv).unwrap();
+        assert!(csv.contains("\npedidos_leidos,6\n"));
+        assert!(csv.contains("\nid,dispensador,"));
+        let md = reporte.formatear(FormatoReporte::Markdown).unwrap();
+        assert!(md.contains("| pedidos_rechazados | 4 |"));
+
+        let ruta = std::env::temp_dir().join("cafeteria_test12_reporte.md");
+        assert!(reporte.escribir(ruta.to_str().unwrap()).is_ok());
+        assert_eq!(std::fs::read_to_string(&ruta).unwrap(), md);
+        assert_eq!(
+            FormatoReporte::desde_ruta("reporte.txt"),
+            Err(CafeteriaError::FormatoInvalido)
+        );
+    }
+
+    #[test]
+    fn test13_comparacion() {
+        let mut anterior = Reporte::generar(&Cafetera::new()).unwrap();
+        anterior.duracion_ms = 10000.0;
+        anterior.estadisticas.cant_pedidos = 10;
+        anterior.estadisticas.granos_consumidos = 100;
+        let mut actual = anterior.clone();
+        actual.duracion_ms = 20000.0;
+        actual.estadisticas.granos_consumidos = 102;
+        actual.estadisticas.reposiciones_cafe = 1;
+
+        let ruta = std::env::temp_dir().join("cafeteria_test13_anterior.json");
+        anterior.escribir(ruta.to_str().unwrap()).unwrap();
+        let anterior = Reporte::leer(ruta.to_str().unwrap()).unwrap();
+
+        let comparacion = Comparacion::new(&anterior, &actual, 5.0);
+        let regresiones: Vec<&str> = comparacion
+            .regresiones()
+            .iter()
+            .map(|d| d.metrica.as_str())
+            .collect();
+        assert_eq!(regresiones, vec!["throughput", "reposiciones_cafe"]);
+        assert!(comparacion
+            .lineas()
+            .last()
+            .unwrap()
+            .starts_with("2 regresiones"));
+    }
+
+    #[test]
+    fn test14_log_archivo() {
+        let ruta = std::env::temp_dir().join("cafeteria_test14.log");
+        log::set_archivo(Some(ruta.to_str().unwrap())).unwrap();
+        log::set_nivel(Nivel::Debug);
+        cafeteria::debug!("mensaje de prueba {}", 14);
+        log::set_nivel(Nivel::Warn);
+        cafeteria::info!("mensaje descartado");
+        log::set_nivel(Nivel::Info);
+        log::set_archivo(None).unwrap();
+
+        let contenido = std::fs::read_to_string(&ruta).unwrap();
+        assert!(contenido.contains("[DEBUG] mensaje de prueba 14\n"));
+        assert!(!contenido.contains("mensaje descartado"));
+        assert_eq!("warn".parse::<Nivel>(), Ok(Nivel::Warn));
+        assert!("verbose".parse::<Nivel>().is_err());
+    }
+
+    #[test]
+    fn test15_intervalo_estadisticas() {
+        let mut configuracion = Configuracion::new();
+        configuracion.tiempo_stats = 60000;
+        let cafetera = Cafetera::con_configuracion(configuracion);
+        cafetera.solicitar_estadisticas();
+        let inicio = Instant::now();
+        let res = cafetera.realizar_pedidos("tests/test03.txt");
+        assert!(res.is_ok());
+        assert!(inicio.elapsed() < Duration::from_millis(60000));
+        assert_eq!(cafetera.snapshot().cant_pedidos, 1);
+    }
+
+    #[test]
+    fn test16_apagado_abortar() {
+        let mut configuracion = Configuracion::new();
+        configuracion.modo_apagado = ModoApagado::Abortar;
+        let cafetera = Cafetera::con_configuracion(configuracion);
+        let remota = cafetera.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(1500));
+            remota.detener();
+        });
+        let res = cafetera.realizar_pedidos("tests/test04.txt");
+        handle.join().unwrap();
+        assert!(res.is_ok());
+        assert!(cafetera.detenida());
+        let estadisticas = cafetera.snapshot();
+        assert!(estadisticas.pedidos_leidos < 10);
+        assert!(estadisticas.pedidos_abortados > 0);
+        assert_eq!(
+            estadisticas.cant_pedidos + estadisticas.pedidos_abortados,
+            cafetera.resultados.lock().unwrap().len() as u32
+        );
+        assert!(estadisticas
+            .dispensadores
+            .iter()
+            .all(|&d| d == EstadoDispensador::Libre));
+    }
+
+    #[test]
+    fn test17_pausa() {
+        let cafetera = Cafetera::new();
+        cafetera.pausar().unwrap();
+        let remota = cafetera.clone();
+        let handle = thread::spawn(move || remota.realizar_pedidos("tests/test03.txt"));
+        thread::sleep(Duration::from_millis(1000));
+        let estadisticas = cafetera.snapshot();
+        assert!(estadisticas.pausada);
+        assert!(estadisticas
+            .dispensadores
+            .iter()
+            .all(|&d| d == EstadoDispensador::Libre));
+        cafetera.ejecutar("resume").unwrap();
+        assert!(handle.join().unwrap().is_ok());
+        let estadisticas = cafetera.snapshot();
+        assert!(!estadisticas.pausada);
+        assert!(estadisticas.tiempo_pausado_ms >= 1000);
+        assert_eq!(estadisticas.cant_pedidos, 1);
+        assert_eq!(
+            cafetera.ejecutar("descansar"),
+            Err(CafeteriaError::ArgumentosInvalidos)
+        );
+    }
+
+    #[test]
+    fn test18_cancelacion() {
+        let cafetera = Cafetera::new();
+        let remota = cafetera.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(1200));
+            assert_eq!(remota.cancelar(1).unwrap(), Cancelacion::EnCurso);
+        });
+        let res = cafetera.realizar_pedidos("tests/test06.txt");
+        handle.join().unwrap();
+        assert!(res.is_ok());
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.pedidos_leidos, 4);
+        assert_eq!(estadisticas.cant_pedidos, 1);
+        assert_eq!(estadisticas.pedidos_cancelados, 2);
+        assert_eq!(
+            estadisticas.desperdicio,
+            Desperdicio {
+                agua: 5,
+                cafe: 5,
+                espuma: 0
+            }
+        );
+        // Los pedidos finalizados ya no pueden cancelarse, y los que todavía no llegaron se
+        // descartarán al llegar
+        assert_eq!(cafetera.cancelar(2).unwrap(), Cancelacion::Finalizado);
+        assert_eq!(cafetera.cancelar(3).unwrap(), Cancelacion::Finalizado);
+        assert_eq!(cafetera.cancelar(7).unwrap(), Cancelacion::Anticipada);
+        assert_eq!(cafetera.snapshot().pedidos_cancelados, 2);
+        // El cafe servido al pedido cancelado también ocupa la canilla
+        let utilizacion = cafetera.utilizacion().unwrap();
+        assert!(utilizacion.canilla_cafe >= Duration::from_millis(1000));
+        assert!(utilizacion.canilla_espuma >= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test19_plazo_pedidos() {
+        let cafetera = Cafetera::new();
+        let res = cafetera.realizar_pedidos("tests/test07.txt");
+        assert!(res.is_ok());
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.cant_pedidos, 1);
+        assert_eq!(estadisticas.pedidos_vencidos, 1);
+        assert_eq!(estadisticas.tasa_abandono(), 50.0);
+        assert_eq!(estadisticas.desperdicio.agua, 5);
+        assert!(estadisticas
+            .dispensadores
+            .iter()
+            .all(|&d| d == EstadoDispensador::Libre));
+
+        let mut configuracion = Configuracion::new();
+        configuracion.plazo = Some(300);
+        let cafetera = Cafetera::con_configuracion(configuracion);
+        let res = cafetera.realizar_pedidos("tests/test03.txt");
+        assert!(res.is_ok());
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.cant_pedidos, 0);
+        assert_eq!(estadisticas.pedidos_vencidos, 1);
+    }
+
+    #[test]
+    fn test20_llegadas_simultaneas() {
+        let cafetera = Cafetera::new();
+        let inicio = Instant::now();
+        let res = cafetera.realizar_pedidos("tests/test08.txt");
+        assert!(res.is_ok());
+        assert!(inicio.elapsed() < Duration::from_millis(4 * TIEMPO_CAFE));
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.cant_pedidos, 5);
+        assert_eq!(estadisticas.clientes_en_cola, 0);
+        let tiempos = cafetera.tiempos.lock().unwrap();
+        let esperaron = tiempos
+            .iter()
+            .filter(|t| t.espera_dispensador() > Duration::from_millis(TIEMPO_CAFE))
+            .count();
+        assert_eq!(esperaron, 2);
+    }
+
+    #[test]
+    fn test21_generador() {
+        let mut generador = Generador::new(30);
+        generador.llegadas = "poisson:30".parse().unwrap();
+        generador.mezcla = Some("latte=1".parse().unwrap());
+        generador.semilla = Some(42);
+        let pedidos = generador.generar();
+        let lineas: Vec<String> = pedidos.iter().map(Pedido::to_line).collect();
+        let otras: Vec<String> = generador.generar().iter().map(Pedido::to_line).collect();
+        assert_eq!(lineas, otras);
+        let latte = Bebida::buscar("latte").unwrap();
+        assert!(pedidos
+            .iter()
+            .all(|p| (p.agua, p.cafe, p.espuma) == (latte.agua, latte.cafe, latte.espuma)));
+        let llegadas: Vec<u64> = pedidos.iter().map(|p| p.llegada.unwrap()).collect();
+        assert!(llegadas.windows(2).all(|w| w[0] <= w[1]));
+
+        generador.llegadas = ModeloLlegadas::Rafagas {
+            tasa: 30.0,
+            tamano: 3,
+        };
+        let pedidos = generador.generar();
+        assert_eq!(pedidos.len(), 30);
+        assert!(pedidos
+            .chunks(3)
+            .all(|grupo| grupo.iter().all(|p| p.llegada == grupo[0].llegada)));
+
+        assert!("poisson:0".parse::<ModeloLlegadas>().is_err());
+        assert!("profile:10".parse::<ModeloLlegadas>().is_err());
+        assert!("mocha=10".parse::<Mezcla>().is_err());
+        assert!("latte=0".parse::<Mezcla>().is_err());
+        assert!("latte=4294967295,espresso=1".parse::<Mezcla>().is_err());
+        let mezcla: Mezcla = "latte=4294967294,espresso=1".parse().unwrap();
+        generador.mezcla = Some(mezcla);
+        assert_eq!(generador.generar().len(), 30);
+        generador.mezcla = Some(Mezcla(vec![(latte, u32::MAX), (latte, u32::MAX)]));
+        assert_eq!(generador.generar().len(), 30);
+    }
+
+    #[test]
+    fn test22_cola_limitada() {
+        let mut configuracion = Configuracion::new();
+        configuracion.paciencia = Some(500);
+        let cafetera = Cafetera::con_configuracion(configuracion);
+        let res = cafetera.realizar_pedidos("tests/test08.txt");
+        assert!(res.is_ok());
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.cant_pedidos, 3);
+        assert_eq!(estadisticas.clientes_abandonados, 2);
+        assert_eq!(estadisticas.clientes_perdidos(), 2);
+        assert_eq!(estadisticas.ingresos_perdidos, 2 * u64::from(PRECIO_PEDIDO));
+
+        let mut configuracion = Configuracion::new();
+        configuracion.largo_cola = Some(0);
+        let cafetera = Cafetera::con_configuracion(configuracion);
+        let res = cafetera.realizar_pedidos("tests/test08.txt");
+        assert!(res.is_ok());
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.cant_pedidos, 0);
+        assert_eq!(estadisticas.clientes_desistidos, 5);
+    }
+
+    #[test]
+    fn test23_formato_con_encabezado() {
+        let mut lector = LectorPedidos::new();
+        assert!(lector.leer("# comentario").unwrap().is_none());
+        assert!(lector.leer("   ").unwrap().is_none());
+        assert!(lector
+            .leer("cliente, plazo, bebida, id, prioridad")
+            .unwrap()
+            .is_none());
+        let Some(Linea::Pedido(pedido)) = lector.leer(" Ana , 3000, Latte, 7, 2 ").unwrap() else {
+            panic!("Se esperaba un pedido");
+        };
+        let latte = Bebida::buscar("latte").unwrap();
+        assert_eq!(pedido.id, 7);
+        assert_eq!(
+            (pedido.agua, pedido.cafe, pedido.espuma),
+            (latte.agua, latte.cafe, latte.espuma)
+        );
+        assert_eq!(pedido.plazo, Some(3000));
+        assert_eq!(pedido.prioridad, 2);
+        assert_eq!(pedido.cliente.as_deref(), Some("Ana"));
+        assert!(lector.leer(",,latte,8,").is_ok());
+        assert!(matches!(
+            lector.leer("cancel, 7"),
+            Ok(Some(Linea::Cancelacion(7)))
+        ));
+        assert!(lector.leer("Ana,3000,latte,9,1,extra").is_err());
+        assert!(lector.leer("Ana,3000,mocha,9,1").is_err());
+
+        let mut lector = LectorPedidos::new();
+        assert!(lector.leer(" 1, 2 ,3,4").unwrap().is_some());
+        assert!(lector.leer("1,2,3,4,garbage").is_err());
+        assert!(lector.leer("1,2,3,4,,,").is_err());
+
+        let mut lector = LectorPedidos::new();
+        assert_eq!(
+            lector.leer("id,agua,cafe,leche").unwrap_err(),
+            CafeteriaError::PedidoInvalido(DetallePedido::new(
+                None,
+                Motivo::Estructura("encabezado invalido: columna desconocida leche".to_string())
+            ))
+        );
+        assert!(lector.leer("1,2,3,4").unwrap().is_some());
+
+        // Una primera línea que no es un encabezado se rechaza sola
+        for primera in ["abcdef", "-1,2,3,4"] {
+            let mut lector = LectorPedidos::new();
+            assert!(matches!(
+                lector.leer(primera),
+                Err(CafeteriaError::PedidoInvalido(detalle)) if detalle.campo == Some("id")
+            ));
+            assert!(lector.leer("1,4,6,5").unwrap().is_some());
+        }
+
+        let cafetera = Cafetera::new();
+        let res = cafetera.realizar_pedidos("tests/test09.txt");
+        assert!(res.is_ok());
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.pedidos_leidos, 2);
+        assert_eq!(estadisticas.cant_pedidos, 2);
+    }
+
+    #[test]
+    fn test24_formatos_json_y_yaml() {
+        assert_eq!(FormatoPedidos::desde_ruta("a.JSON"), FormatoPedidos::Json);
+        assert_eq!(
+            FormatoPedidos::desde_ruta("a.ndjson"),
+            FormatoPedidos::JsonLines
+        );
+        assert_eq!(FormatoPedidos::desde_ruta("a.yml"), FormatoPedidos::Yaml);
+        assert_eq!(FormatoPedidos::desde_ruta("a.txt"), FormatoPedidos::Texto);
+
+        let cafetera = Cafetera::new();
+        let res = cafetera.realizar_pedidos("tests/test10.json");
+        assert!(res.is_ok());
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.pedidos_leidos, 4);
+        assert_eq!(estadisticas.pedidos_rechazados, 1);
+        assert_eq!(estadisticas.cant_pedidos, 3);
+
+        let mut generador = Generador::new(5);
+        generador.mezcla = Some("latte=1,espresso=1".parse().unwrap());
+        generador.semilla = Some(7);
+        let esperados: Vec<String> = generador.generar().iter().map(Pedido::to_line).collect();
+        for (nombre, formato) in [
+            ("cafeteria_test24.json", FormatoPedidos::Json),
+            ("cafeteria_test24.jsonl", FormatoPedidos::JsonLines),
+            ("cafeteria_test24.yaml", FormatoPedidos::Yaml),
+        ] {
+            let ruta = std::env::temp_dir().join(nombre);
+            generador.escribir(ruta.to_str().unwrap()).unwrap();
+            let archivo = std::io::BufReader::new(std::fs::File::open(&ruta).unwrap());
+            let leidos: Vec<String> = formato
+                .entradas(archivo)
+                .unwrap()
+                .map(|linea| match linea.resultado {
+                    Ok(Linea::Pedido(pedido)) => pedido.to_line(),
+                    _ => panic!("Se esperaba un pedido"),
+                })
+                .collect();
+            assert_eq!(leidos, esperados);
+        }
+    }
+
+    #[test]
+    fn test25_errores_detallados() {
+        let detalle = |campo, motivo| {
+            Err(CafeteriaError::PedidoInvalido(DetallePedido::new(
+                campo, motivo,
+            )))
+        };
+        assert_eq!(
+            Pedido::from_line("1,4,x,5").map(|_| ()),
+            detalle(Some("cafe"), Motivo::NoNumerico)
+        );
+        assert_eq!(
+            Pedido::from_line("1,4,11,5").map(|_| ()),
+            detalle(Some("cafe"), Motivo::FueraDeRango)
+        );
+        assert_eq!(
+            Pedido::from_line("1,4,6").map(|_| ()),
+            detalle(Some("espuma"), Motivo::CampoFaltante)
+        );
+        assert_eq!(
+            Pedido::from_line("1,4,6,5,1,2,3").map(|_| ()),
+            detalle(None, Motivo::CamposDeMas)
+        );
+
+        let cafetera = Cafetera::new();
+        let res = cafetera.realizar_pedidos("tests/test05.txt");
+        assert!(res.is_ok());
+        let rechazados = cafetera.rechazados().unwrap();
+        let lineas: Vec<(&str, Option<usize>)> = rechazados
+            .iter()
+            .map(|rechazo| match &rechazo.error {
+                CafeteriaError::PedidoInvalido(detalle) => (rechazo.texto.as_str(), detalle.linea),
+                _ => panic!("Se esperaba un pedido invalido"),
+            })
+            .collect();
+        assert_eq!(
+            lineas,
+            [
+                ("2,1,11,10", Some(2)),
+                ("3,3,5,-1", Some(3)),
+                ("abcdef", Some(4)),
+                ("4,0,7,5", Some(5))
+            ]
+        );
+        assert_eq!(
+            rechazados[0].descripcion(),
+            "linea 2: campo cafe: fuera de rango"
+        );
+
+        let ruta = std::env::temp_dir().join("cafeteria_test25_rechazados.txt");
+        escribir_rechazados(ruta.to_str().unwrap(), &rechazados).unwrap();
+        let contenido = std::fs::read_to_string(&ruta).unwrap();
+        assert!(contenido.starts_with("# linea 2: campo cafe: fuera de rango\n2,1,11,10\n"));
+        assert_eq!(contenido.lines().count(), 8);
+
+        let rechazo = Rechazo {
+            texto: String::new(),
+            error: CafeteriaError::FormatoInvalido,
+        };
+        assert_eq!(rechazo.descripcion(), "formato de archivo invalido");
+        let validacion = Validacion::desde_entradas(
+            FormatoPedidos::Texto
+                .entradas(std::io::Cursor::new("id,agua,cafe,leche\n1,4,6,5\n"))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            validacion.rechazados[0].descripcion(),
+            "linea 1: encabezado invalido: columna desconocida leche"
+        );
+    }
+
+    #[test]
+    fn test26_validacion() {
+        let validacion = Validacion::validar("tests/test05.txt", FormatoPedidos::Texto).unwrap();
+        assert!(!validacion.es_valida());
+        assert_eq!(validacion.pedidos, 2);
+        assert_eq!(validacion.rechazados.len(), 4);
+        assert_eq!(validacion.cafe.consumido, 50);
+        assert_eq!(validacion.espuma.consumido, 50);
+
+        let entradas = FormatoPedidos::Texto
+            .entradas(std::io::Cursor::new(
+                "1,5,5,5\n2,5,5,5\ncancel,1\n1,5,5,5\n",
+            ))
+            .unwrap();
+        let validacion = Validacion::desde_entradas(entradas).unwrap();
+        assert!(!validacion.es_valida());
+        assert_eq!(validacion.duplicados, [1]);
+        assert_eq!(validacion.cancelaciones, 1);
+
+        let consumo = Consumo::estimar(&[10; 30], C, G);
+        assert_eq!(consumo.servido, 300);
+        assert_eq!(consumo.reposiciones, 6);
+        assert_eq!(consumo.recargas, 1);
+
+        assert!(
+            Validacion::validar("tests/test04.txt", FormatoPedidos::Texto)
+                .unwrap()
+                .es_valida()
+        );
+    }
+
+    #[test]
+    fn test27_ids_repetidos() {
+        let correr = |repetidos| {
+            let mut configuracion = Configuracion::new();
+            configuracion.repetidos = repetidos;
+            let cafetera = Cafetera::con_configuracion(configuracion);
+            assert!(cafetera.realizar_pedidos("tests/test11.txt").is_ok());
+            cafetera
+        };
+
+        let cafetera = correr(PoliticaRepetidos::Rechazar);
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.pedidos_repetidos, 2);
+        assert_eq!(estadisticas.pedidos_rechazados, 2);
+        assert_eq!(estadisticas.cant_pedidos, 2);
+        assert!(cafetera
+            .rechazados()
+            .unwrap()
+            .iter()
+            .all(|rechazo| matches!(
+                &rechazo.error,
+                CafeteriaError::PedidoInvalido(detalle) if detalle.motivo == Motivo::IdRepetido
+            )));
+
+        let cafetera = correr(PoliticaRepetidos::Renombrar);
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.pedidos_repetidos, 2);
+        assert_eq!(estadisticas.cant_pedidos, 4);
+        assert_eq!(cafetera.resultado(3).unwrap(), Some(Resultado::Completado));
+        assert_eq!(cafetera.resultado(4).unwrap(), Some(Resultado::Completado));
+
+        // Sin un id mayor al máximo recibido, el pedido repetido se rechaza
+        let mut configuracion = Configuracion::new();
+        configuracion.repetidos = PoliticaRepetidos::Renombrar;
+        let cafetera = Cafetera::con_configuracion(configuracion);
+        let linea = format!("{},1,1,1", usize::MAX);
+        let entradas: Vec<Entrada> = (0..2)
+            .map(|_| Entrada {
+                texto: linea.clone(),
+                resultado: Linea::from_line(&linea),
+            })
+            .collect();
+        assert!(cafetera
+            .realizar_entradas(Box::new(entradas.into_iter()))
+            .is_ok());
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.pedidos_rechazados, 1);
+        assert_eq!(estadisticas.cant_pedidos, 1);
+
+        let cafetera = correr(PoliticaRepetidos::Idempotente);
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.pedidos_repetidos, 2);
+        assert_eq!(estadisticas.pedidos_rechazados, 0);
+        assert_eq!(estadisticas.cant_pedidos, 2);
+        assert_eq!(cafetera.resultado(5).unwrap(), None);
+    }
+
+    #[test]
+    fn test28_linea_de_comandos() {
+        let parsear = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            cli::parsear(&args)
+        };
+
+        assert!(matches!(parsear(&[]), Ok(Comando::Ayuda(_))));
+        assert!(matches!(
+            parsear(&["validate", "--help"]),
+            Ok(Comando::Ayuda(ayuda)) if ayuda == Subcomando::Validate.ayuda()
+        ));
+
+        let Ok(Comando::Correr { ruta, opciones }) = parsear(&[
+            "run",
+            "pedidos.txt",
+            "-q",
+            "--queue-size",
+            "4",
+            "--shutdown",
+            "abort",
+            "--format",
+            "jsonl",
+        ]) else {
+            panic!("Se esperaba el comando run");
+        };
+        assert_eq!(ruta, "pedidos.txt");
+        assert_eq!(opciones.nivel, Some(Nivel::Warn));
+        assert_eq!(opciones.configuracion.largo_cola, Some(4));
+        assert_eq!(opciones.configuracion.modo_apagado, ModoApagado::Abortar);
+        assert_eq!(opciones.formato, Some(FormatoPedidos::JsonLines));
+        assert!(matches!(
+            parsear(&["pedidos.txt", "--report", "r.md"]),
+            Ok(Comando::Correr { .. })
+        ));
+
+        let Ok(Comando::Generar { generador, .. }) =
+            parsear(&["-f", "pedidos.txt", "20", "--seed", "3"])
+        else {
+            panic!("Se esperaba el comando generate");
+        };
+        assert_eq!(generador.cantidad, 20);
+        assert_eq!(generador.semilla, Some(3));
+
+        for invalidos in [
+            &["generate", "pedidos.txt", "muchos"][..],
+            &["run", "pedidos.txt", "--queue-size", "-1"],
+            &["run", "pedidos.txt", "--deadline"],
+            &["run", "pedidos.txt", "--stats-interval", "0"],
+            &["run", "pedidos.txt", "--report", "reporte.txt"],
+            &["run"],
+            &["simulate", "--bogus"],
+            &["compare", "a.json"],
+            &["compare", "a.json", "b.json", "--threshold", "-1"],
+            &["compare", "a.json", "b.json", "--threshold", "NaN"],
+            &["compare", "a.json", "b.json", "--threshold", "inf"],
+            &["compare", "a.json", "b.json", "--umbral", "10"],
+            &["--bogus"],
+        ] {
+            assert!(parsear(invalidos).is_err(), "{:?}", invalidos);
+        }
+        assert!(matches!(
+            parsear(&["compare", "a.json", "b.json", "--threshold", "10"]),
+            Ok(Comando::Comparar { umbral, .. }) if umbral == 10.0
+        ));
+        let error = parsear(&["validate", "a.txt", "b.txt"]).unwrap_err();
+        assert_eq!(error.subcomando, Some(Subcomando::Validate));
+    }
+
+    #[test]
+    fn test29_errores_y_codigos_de_salida() {
+        let error = Cafetera::new()
+            .realizar_pedidos("tests/no_existe.txt")
+            .unwrap_err();
+        let causa = error.source().expect("Se esperaba la causa del error");
+        assert_eq!(
+            error.descripcion(),
+            format!("no se pudo abrir el archivo: {}", causa)
+        );
+        assert_eq!(error.codigo_salida(), SALIDA_ARCHIVO);
+
+        let mut detalle = DetallePedido::new(Some("cafe"), Motivo::FueraDeRango);
+        detalle.linea = Some(3);
+        let error = CafeteriaError::from(detalle);
+        assert_eq!(
+            error.descripcion(),
+            "pedido invalido: linea 3: campo cafe: fuera de rango"
+        );
+        assert_eq!(error.codigo_salida(), SALIDA_PEDIDOS_INVALIDOS);
+        assert_eq!(
+            CafeteriaError::ArgumentosInvalidos.codigo_salida(),
+            SALIDA_ARGUMENTOS
+        );
+        assert_eq!(
+            CafeteriaError::LockEnvenenado.codigo_salida(),
+            SALIDA_LOCK_ENVENENADO
+        );
+
+        let args: Vec<String> = ["run", "pedidos.txt", "--strict"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert!(matches!(
+            cli::parsear(&args),
+            Ok(Comando::Correr { opciones, .. }) if opciones.estricto
+        ));
+    }
+
+    #[test]
+    fn test30_recuperacion_de_locks() {
+        let cafetera = Cafetera::new();
+        let cafe = cafetera.cafe.clone();
+        let envenenador = thread::spawn(move || {
+            let mut contenedor = cafe.0.lock().unwrap();
+            contenedor.cafe_molido = contenedor.cafe_molido.wrapping_sub(1);
+            panic!("Panico con el lock del cafe tomado");
+        });
+        assert!(envenenador.join().is_err());
+        assert!(cafetera.cafe.0.is_poisoned());
+
+        let estadisticas = cafetera.snapshot();
+        assert!(!cafetera.cafe.0.is_poisoned());
+        assert_eq!(estadisticas.cafe_molido, 0);
+        assert_eq!(estadisticas.locks_recuperados, 1);
+
+        assert!(cafetera.realizar_pedidos("tests/test08.txt").is_ok());
+        assert_eq!(cafetera.snapshot().cant_pedidos, 5);
+
+        let (resultados, lectura) = (cafetera.resultados.clone(), cafetera.lectura.clone());
+        let envenenador = thread::spawn(move || {
+            let _resultados = resultados.lock().unwrap();
+            let _lectura = lectura.lock().unwrap();
+            panic!("Panico con los contadores tomados");
+        });
+        assert!(envenenador.join().is_err());
+        assert!(cafetera.resultados.is_poisoned() && cafetera.lectura.is_poisoned());
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.cant_pedidos, 5);
+        assert_eq!(estadisticas.pedidos_leidos, 5);
+    }
+
+    #[test]
+    fn test31_aislamiento_de_panicos() {
+        assert_eq!(aislar(|| 3), Ok(3));
+        assert_eq!(
+            aislar(|| -> u32 { panic!("Pedido {} sin vaso", 7) }),
+            Err("Pedido 7 sin vaso".to_string())
+        );
+        assert_eq!(
+            aislar(|| -> u32 { panic!("Sin vasos") }),
+            Err("Sin vasos".to_string())
+        );
+
+        let args: Vec<String> = ["simulate", "--retries", "2"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert!(matches!(
+            cli::parsear(&args),
+            Ok(Comando::Simular { opciones, .. }) if opciones.configuracion.reintentos == 2
+        ));
+
+        let cafetera = Cafetera::new();
+        assert!(cafetera.realizar_pedidos("tests/test08.txt").is_ok());
+        assert!(cafetera.fallas().unwrap().is_empty());
+        assert_eq!(cafetera.snapshot().pedidos_fallidos, 0);
+    }
+
+    #[test]
+    fn test32_reanudar_durante_snapshot() {
+        let cafetera = Cafetera::new();
+        cafetera.pausar().unwrap();
+
+        // Simula una reposición que retiene el contenedor de café
+        let cafe = cafetera.cafe.clone();
+        let reposicion = thread::spawn(move || {
+            let _contenedor = cafe.0.lock().unwrap();
+            thread::sleep(Duration::from_millis(500));
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let snapshot = cafetera.clone();
+        let tx_snapshot = tx.clone();
+        thread::spawn(move || {
+            let _ = snapshot.snapshot();
+            let _ = tx_snapshot.send(true);
+        });
+        thread::sleep(Duration::from_millis(100));
+        let reanudar = cafetera.clone();
+        thread::spawn(move || {
+            let _ = tx.send(reanudar.reanudar().is_ok());
+        });
+
+        for _ in 0..2 {
+            assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(true));
+        }
+        reposicion.join().unwrap();
+        assert!(!cafetera.pausada().unwrap());
+    }
+
+    #[test]
+    fn test33_panico_en_llegadas() {
+        let cafetera = Cafetera::new();
+        let mut entradas = vec![Entrada {
+            texto: "1,1,1,1".to_string(),
+            resultado: Linea::from_line("1,1,1,1"),
+        }]
+        .into_iter();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let corrida = cafetera.clone();
+        thread::spawn(move || {
+            let resultado = corrida.realizar_entradas(Box::new(std::iter::from_fn(move || {
+                Some(
+                    entradas
+                        .next()
+                        .unwrap_or_else(|| panic!("Error al leer los pedidos")),
+                )
+            })));
+            let _ = tx.send(resultado.is_ok());
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(30)), Ok(true));
+        assert_eq!(cafetera.snapshot().cant_pedidos, 1);
+    }
+
+    #[test]
+    fn test34_panico_en_preparacion() {
+        let mut configuracion = Configuracion::new();
+        configuracion.reintentos = 1;
+        let cafetera = Cafetera::con_inyeccion_fallas(configuracion, |pedido, intento| {
+            if pedido.id == 2 || (pedido.id == 3 && intento == 0) {
+                panic!("Pedido {} sin vaso", pedido.id);
+            }
+        });
+        assert!(cafetera.realizar_pedidos("tests/test08.txt").is_ok());
+
+        let estadisticas = cafetera.snapshot();
+        assert!(estadisticas
+            .dispensadores
+            .iter()
+            .all(|estado| *estado == EstadoDispensador::Libre));
+        assert_eq!(estadisticas.cant_pedidos, 4);
+        assert_eq!(estadisticas.pedidos_fallidos, 1);
+        assert!(cafetera
+            .resultados
+            .lock()
+            .unwrap()
+            .contains(&(2, Resultado::Fallido)));
+        assert_eq!(
+            cafetera.fallas().unwrap(),
+            vec![(2, "Pedido 2 sin vaso".to_string())]
+        );
+        // El agua del pedido 2 se sirvió en ambos intentos y la del 3 en el primero
+        assert_eq!(
+            estadisticas.desperdicio,
+            Desperdicio {
+                agua: 3,
+                cafe: 0,
+                espuma: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test35_utilizacion_sin_completar() {
+        let cafetera = Cafetera::con_inyeccion_fallas(Configuracion::new(), |pedido, _| {
+            panic!("Pedido {} sin vaso", pedido.id)
+        });
+        assert!(cafetera.realizar_pedidos("tests/test08.txt").is_ok());
+        assert_eq!(cafetera.snapshot().pedidos_fallidos, 5);
+        let utilizacion = cafetera.utilizacion().unwrap();
+        let ocupado: Duration = utilizacion.dispensadores.iter().map(|d| d.ocupado).sum();
+        assert!(ocupado >= Duration::from_millis(500));
+
+        // La pausa ocurre mientras el único pedido sirve el agua
+        let cafetera = Cafetera::new();
+        let entradas = vec![Entrada {
+            texto: "1,10,1,1".to_string(),
+            resultado: Linea::from_line("1,10,1,1"),
+        }];
+        let corrida = cafetera.clone();
+        let handle =
+            thread::spawn(move || corrida.realizar_entradas(Box::new(entradas.into_iter())));
+        thread::sleep(Duration::from_millis(300));
+        cafetera.pausar().unwrap();
+        thread::sleep(Duration::from_millis(400));
+        cafetera.reanudar().unwrap();
+        assert!(handle.join().unwrap().is_ok());
+
+        let utilizacion = cafetera.utilizacion().unwrap();
+        assert!(utilizacion.pausa >= Duration::from_millis(400));
+        let (asignados, libres): (Vec<&UtilizacionDispensador>, Vec<_>) = utilizacion
+            .dispensadores
+            .iter()
+            .partition(|d| d.ocupado > Duration::ZERO);
+        assert_eq!(asignados.len(), 1);
+        for d in asignados {
+            assert_eq!(d.ocupado + d.bloqueado + d.libre, utilizacion.duracion);
+        }
+        for d in libres {
+            assert_eq!(d.libre, utilizacion.duracion - utilizacion.pausa);
+        }
+    }
+
+    #[test]
+    fn test36_pedido_vencido_en_cola() {
+        let cafetera = Cafetera::new();
+        let entradas: Vec<Entrada> = [
+            "1,1,1,1,1500,0",
+            "2,1,1,1,1500,0",
+            "3,1,1,1,1500,0",
+            "4,1,1,1,,0",
+            "5,5,1,1,500,0",
+        ]
+        .iter()
+        .map(|linea| Entrada {
+            texto: linea.to_string(),
+            resultado: Linea::from_line(linea),
+        })
+        .collect();
+        assert!(cafetera
+            .realizar_entradas(Box::new(entradas.into_iter()))
+            .is_ok());
+
+        // Los pedidos 1 a 3 vencen esperando el cafe y liberan sus dispensadores cuando el
+        // pedido 5 ya vencio en la cola, por lo que solo se desperdicia su agua
+        let estadisticas = cafetera.snapshot();
+        assert_eq!(estadisticas.cant_pedidos, 1);
+        assert_eq!(estadisticas.pedidos_vencidos, 4);
+        assert!(cafetera
+            .resultados
+            .lock()
+            .unwrap()
+            .contains(&(5, Resultado::Timeout)));
+        assert_eq!(
+            estadisticas.desperdicio,
+            Desperdicio {
+                agua: 3,
+                cafe: 0,
+                espuma: 0
+            }
+        );
+    }
 }